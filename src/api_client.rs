@@ -1,9 +1,6 @@
-use crate::error::ReqError;
-use chrono::offset;
-use std::env;
-use std::time::Duration;
 use serde::{Serialize, Deserialize};
 use chrono::{Utc, DateTime};
+use sha1::{Digest, Sha1};
 use url::Url;
 
 // import ureq Response struct
@@ -483,39 +480,181 @@ pub struct PullRequestLink {
     pub href: Url,
 }
 #[derive(Serialize, Deserialize, Debug)]
-struct Content {
+pub struct Content {
     #[serde(rename = "ref")]
-    ref_: String,
-    node_id: String,
-    url: String,
-    object: Object,
+    pub ref_: String,
+    pub node_id: String,
+    pub url: String,
+    pub object: Object,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Object {
-    sha: String,
+pub struct Object {
+    pub sha: String,
     #[serde(rename = "type")]
-    type_: String,
-    url: String,
+    pub type_: String,
+    pub url: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct TreeEntry {
-    sha: String,
-    url: String,
-    tree: Vec<TreeObject>,
-    truncated: bool,
+pub(crate) struct TreeEntry {
+    pub(crate) sha: String,
+    pub(crate) url: String,
+    pub(crate) tree: Vec<TreeObject>,
+    // set when GitHub cut a recursive listing short (100,000 entries or 7MB)
+    #[serde(default)]
+    pub(crate) truncated: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TreeObject {
+    pub path: String,
+    pub mode: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub size: Option<u64>,
+    pub sha: String,
+    // submodule ("commit") entries have no url
+    pub url: Option<String>,
+}
+
+// A file to be committed. Its contents are uploaded as a blob before the tree
+// is created.
+#[derive(Debug, Clone)]
+pub struct TreeFile {
+    pub path: String,
+    pub mode: String,
+    pub content: Vec<u8>,
+}
+
+impl TreeFile {
+    // A regular (non-executable) file
+    pub fn new(path: impl Into<String>, content: impl Into<Vec<u8>>) -> Self {
+        TreeFile {
+            path: path.into(),
+            mode: "100644".to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewTreeEntry {
+    pub path: String,
+    pub mode: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub sha: String,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct NewTree<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) base_tree: Option<&'a str>,
+    pub(crate) tree: &'a [NewTreeEntry],
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct NewCommit<'a> {
+    pub(crate) message: &'a str,
+    pub(crate) tree: &'a str,
+    pub(crate) parents: &'a [String],
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NewPullRequest {
+    pub title: String,
+    pub head: String,
+    pub base: String,
+    pub maintainer_can_modify: bool,
+    pub draft: bool,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct NewFork<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) organization: Option<&'a str>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct UpstreamBranch<'a> {
+    pub(crate) branch: &'a str,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct MergeUpstream {
+    pub(crate) message: Option<String>,
+    // "fast-forward", "merge" or "none"
+    pub(crate) merge_type: Option<String>,
+    pub(crate) base_branch: Option<String>,
+}
+
+// Body of a Contents API write, which unlike the Git Data API also works on a
+// repository without any commits
+#[derive(Serialize, Debug)]
+pub(crate) struct NewContentFile<'a> {
+    pub(crate) message: &'a str,
+    pub(crate) content: String,
+    pub(crate) branch: &'a str,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct NewBlob {
+    pub(crate) content: String,
+    pub(crate) encoding: &'static str,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct BlobRef {
+    pub(crate) sha: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct TreeObject {
-    path: String,
-    mode: String,
+pub struct Blob {
+    pub sha: String,
+    pub node_id: String,
+    pub size: Option<u64>,
+    pub url: String,
+    pub content: String,
+    pub encoding: String,
+}
+
+// An entry returned by the Contents API. Directory listings leave out the
+// content, as do files over 1MB (whose encoding is then "none").
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ContentFile {
     #[serde(rename = "type")]
-    type_: String,
-    size: Option<u64>,
-    sha: String,
-    url: String,
+    pub type_: String,
+    pub name: String,
+    pub path: String,
+    pub sha: String,
+    pub size: u64,
+    pub encoding: Option<String>,
+    pub content: Option<String>,
+    pub url: String,
+    pub html_url: Option<String>,
+    pub git_url: Option<String>,
+    pub download_url: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum Contents {
+    File(Box<ContentFile>),
+    Listing(Vec<ContentFile>),
+}
+
+// The SHA git assigns to a blob with these contents, so blobs that already
+// exist in the repository can be detected without uploading them
+pub fn blob_sha(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -526,23 +665,23 @@ pub struct Commit {
     pub html_url: String,
     author: Author,
     committer: Author,
-    message: String,
-    tree: Tree,
+    pub message: String,
+    pub tree: Tree,
     pub parents: Vec<Parent>,
     verification: Verification,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Author {
-    name: String,
-    email: String,
-    date: String,
+pub struct Author {
+    pub name: String,
+    pub email: String,
+    pub date: String,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
-struct Tree {
-    sha: String,
-    url: String,
+pub struct Tree {
+    pub sha: String,
+    pub url: String,
 }
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Parent {
@@ -559,221 +698,249 @@ struct Verification {
     payload: Option<String>,
 }
 
-fn get_github_token() -> String {
-    match env::var("GITHUB_AUTH_TOKEN") {
-        Ok(val) => format!("Token {}", val),
-        Err(_err) => ("").to_string(),
-    }
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Comparison {
+    pub url: String,
+    pub html_url: String,
+    // "identical", "ahead", "behind" or "diverged"
+    pub status: String,
+    pub ahead_by: i64,
+    pub behind_by: i64,
+    pub total_commits: i64,
 }
 
-// Get the head SHA of the repository (required for get_ref)
-pub fn get_base(owner: &str, repo: &str, base_ref: &str) -> Result<String, ReqError> {
-    let url = format!("https://api.github.com/repos/{}/{}/git/refs/heads/{}", owner, repo, base_ref);
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .get(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "acme-rs")
-        .timeout(Duration::from_secs(5))
-        .send()?;
-    println!("get_base HTTP code: {:?}", response.status());
-    let data: Content = response.json()?;
-    Ok(data.object.sha)
-}
-
-// Check if named ref exists, if not (404 not found) create it
-pub fn get_ref(owner: &str, repo: &str, gitref: String, head_sha: String) -> Result<String, ReqError> {
-    let client = reqwest::blocking::Client::new();
-    let url = format!("https://api.github.com/repos/{}/{}/git/{}", owner, repo, gitref);
-    let response = client
-        .get(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .send()?;
-
-    if response.status() == 404 {
-        return create_ref(owner, repo, gitref, head_sha)
-    } else {
-        return Err(ReqError::BadRequest)
-    }
+// A commit as returned by the repository commits API, which wraps the git
+// commit data in a `commit` field
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RepoCommit {
+    pub sha: String,
+    pub node_id: String,
+    pub url: String,
+    pub html_url: String,
+    pub commit: CommitSummary,
+    pub parents: Vec<Parent>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CommitSummary {
+    pub message: String,
+    pub tree: Tree,
+    pub author: Option<Author>,
+    pub committer: Option<Author>,
 }
 
-// Create a ref
-fn create_ref(owner: &str, repo: &str, gitref: String, head_sha: String) -> Result<String, ReqError> {
-    println!("Creating ref: {}", gitref);
-    let url: String = format!("https://api.github.com/repos/{}/{}/git/refs", owner, repo);
-    let body = format!(
-        r#"{{
-            "ref": "{}",
-            "sha": "{}"
-        }}"#,
-        gitref, head_sha
-    );
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .body(body)
-        .send()?;
-    println!("create_ref HTTP code {:?}", response.status());
-    let data: Content = response.json()?;
-    let ref_name = data.ref_;
-    Ok(ref_name)
-}
-
-// Create a ref
-fn update_ref(owner: &str, repo: &str, current_ref: String, new_sha: String) -> Result<String, ReqError> {
-    let url: String = format!("https://api.github.com/repos/{}/{}/git/{}", owner, repo, current_ref);
-    let body = format!(
-        r#"{{
-            "sha": "{}",
-            "force": true          
-        }}"#, new_sha
-    );
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .patch(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .body(body)
-        .send()?;
-    println!("update_ref HTTP code {:?}", response.status());
-    let data: Content = response.json()?;
-    let ref_name = data.ref_;
-    Ok(ref_name)
-}
-
-// build the files into an array and create a git tree
-pub fn create_tree(owner: &str, repo: &str, head_sha: String) -> Result<String, ReqError> {
-    let url: String = format!("https://api.github.com/repos/{}/{}/git/trees", owner, repo);
-    let body = format!(
-        r#"{{
-            "base_tree": "{}",
-            "tree": [
-                {{
-                    "path": "README.md",
-                    "mode": "100644",
-                    "type": "blob",
-                    "content": "Hello World!"
-                }}
-            ]
-        }}"#,
-        head_sha.to_string()
-    );
-
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .body(body)
-        .send()?;
-
-    println!("Create tree: {:?}", response.status());
-    let data: TreeEntry = response.json()?;
-    let tree_sha = data.sha;
-    Ok(tree_sha)
-}
-
-pub fn get_parent_commit(owner: &str, repo: &str, head_sha: String) -> Result<Commit, ReqError> {
-     let url: String = format!("https://api.github.com/repos/{}/{}/git/commits/{}", owner, repo, head_sha);
-     let client = reqwest::blocking::Client::new();
-     let response = client
-        .get(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "acme-rs")
-        .timeout(Duration::from_secs(5))
-        .send()?;
-    println!("get_commit HTTP code: {:?}", response.status());
-    let data: Commit = response.json()?;
-    println!("data in get_parent: {:?}", data);
-    Ok(data)
-}
-
-// create the commit
-pub fn push_commit(current_ref: String, owner: &str, repo: &str,  tree_sha: String, parents: Vec<Parent>) -> Result<String, ReqError> {
-    let _date_now = offset::Local::now();
-    let url: String = format!("https://api.github.com/repos/{}/{}/git/commits", owner, repo);
-    let mut parent_sha = Vec::new();
-    for i in &parents {
-        let i = i; // elements are immutable pointers
-        parent_sha.push(&i.sha);
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusState {
+    Error,
+    Failure,
+    Pending,
+    Success,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewStatus {
+    pub state: StatusState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub context: String,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Status {
+    pub id: i64,
+    pub url: String,
+    pub state: StatusState,
+    pub description: Option<String>,
+    pub target_url: Option<String>,
+    pub context: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRunStatus {
+    Queued,
+    InProgress,
+    Completed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckRunConclusion {
+    ActionRequired,
+    Cancelled,
+    Failure,
+    Neutral,
+    Success,
+    Skipped,
+    Stale,
+    TimedOut,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationLevel {
+    Notice,
+    Warning,
+    Failure,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Annotation {
+    pub path: String,
+    pub start_line: u32,
+    pub end_line: u32,
+    pub annotation_level: AnnotationLevel,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct CheckRunOutput {
+    pub title: String,
+    pub summary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<Annotation>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewCheckRun {
+    pub name: String,
+    pub head_sha: String,
+    pub status: CheckRunStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conclusion: Option<CheckRunConclusion>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<CheckRunOutput>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CheckRun {
+    pub id: i64,
+    pub head_sha: String,
+    pub url: String,
+    pub html_url: Option<String>,
+    pub name: String,
+    pub status: CheckRunStatus,
+    pub conclusion: Option<CheckRunConclusion>,
+}
+
+// Result of verifying a single artifact with sget
+#[derive(Debug, Clone)]
+pub struct ArtifactVerification {
+    pub path: String,
+    pub verified: bool,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct IssueComment {
+    pub id: i64,
+    pub node_id: String,
+    pub url: String,
+    pub html_url: String,
+    pub body: Option<String>,
+    pub user: Option<User>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Debug)]
+pub(crate) struct CommentBody<'a> {
+    pub(crate) body: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewEvent {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum DiffSide {
+    Left,
+    Right,
+}
+
+// A file changed by a pull request. patch is missing for binary files and
+// very large diffs.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PullRequestFile {
+    pub sha: Option<String>,
+    pub filename: String,
+    pub status: String,
+    pub additions: u64,
+    pub deletions: u64,
+    pub changes: u64,
+    pub patch: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ReviewComment {
+    pub path: String,
+    pub body: String,
+    pub line: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<DiffSide>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_side: Option<DiffSide>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct NewReview {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    pub event: ReviewEvent,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<ReviewComment>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReviewLinks {
+    pub html: HtmlLink,
+    pub pull_request: PullRequestLink,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Review {
+    pub id: i64,
+    pub node_id: String,
+    pub user: Option<User>,
+    pub body: Option<String>,
+    pub state: String,
+    pub html_url: String,
+    pub pull_request_url: String,
+    pub commit_id: Option<String>,
+    pub submitted_at: Option<DateTime<Utc>>,
+    #[serde(rename = "_links")]
+    pub links: Option<ReviewLinks>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // expected values from `git hash-object`
+    #[test]
+    fn blob_sha_matches_git() {
+        assert_eq!(blob_sha(b""), "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391");
+        assert_eq!(blob_sha(b"hello world\n"), "3b18e512dba79e4c8300dd08aeb37f8e728b8dad");
     }
-    println!("parent_sha in push_commit : {:?}", parent_sha);
-    let body = format!(
-        r#"{{
-            "message": "Update script",
-            "tree": "{}",
-            "parents": {:?}
-        }}"#,
-        tree_sha, parent_sha
-    );
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .body(body)
-        .send()?;
-    println!("create_commit HTTP code: {:?}", response.status());
-    let data: Commit = response.json()?;
-    let new_sha = data.sha;
-
-    // Attach the commit to the branch that was recently created
-    let new_ref = update_ref(owner, repo, current_ref, new_sha)?;
-    Ok(new_ref)
-
-}
-
-// push the pull request
-pub fn create_pr(owner: &str, repo: &str,  title: &str, head: String, base: String, maintainer_can_modify: bool, draft: bool)  -> Result<String, ReqError> {
-    let url: String = format!("https://api.github.com/repos/{}/{}/pulls", owner, repo);
-    let body = format!(
-        r#"{{
-            "title": "{}",
-            "head": "{}",
-            "base": "{}",
-            "maintainer_can_modify": {},
-            "draft": {}
-        }}"#,
-        title, head, base, maintainer_can_modify, draft
-    );
-    let client = reqwest::blocking::Client::new();
-    let response = client
-        .post(url)
-        .header("Authorization", get_github_token())
-        .header("Accept", "application/vnd.github.v3+json")
-        .header("User-Agent", "SIGSTORE")
-        .timeout(Duration::from_secs(120))
-        .body(body)
-        .send()?;
-    println!("create_pr HTTP code {:?}", response.status());
-    let data: PullRequest = response.json()?;
-    Ok(data.url)
-}
-
-// https://docs.rs/reqwest/latest/reqwest/struct.StatusCode.html
-
-// match response.status() {
-//         reqwest::StatusCode::OK => (),
-//         reqwest::StatusCode::BAD_REQUEST => return Err(ReqError::BadRequest),
-//         reqwest::StatusCode::UNAUTHORIZED => return Err(ReqError::AuthError),
-//         reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE => {
-//             return Err(ReqError::UnsupportedMediaType)
-//         }
-//         reqwest::StatusCode::TOO_MANY_REQUESTS => return Err(ReqError::TooManyRequest),
-//         _ => return Err(ReqError::UnknownConnectionError),
-// }
+}
@@ -0,0 +1,203 @@
+use crate::client::{api_url, check_response, GitHubClient};
+use crate::error::ReqError;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+
+// Installation tokens are valid for an hour; renew them a little before they expire
+const REFRESH_MARGIN_SECS: i64 = 300;
+
+// GitHub rejects app JWTs that expire more than 10 minutes in the future
+const JWT_LIFETIME_SECS: i64 = 540;
+
+#[derive(Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+#[derive(Deserialize, Debug)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct App {
+    slug: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+    // permission name -> "read" or "write", as granted to the installation
+    #[serde(default)]
+    permissions: HashMap<String, String>,
+}
+
+impl InstallationToken {
+    fn is_fresh(&self) -> bool {
+        self.expires_at - Utc::now() > ChronoDuration::seconds(REFRESH_MARGIN_SECS)
+    }
+}
+
+// Shared by every client in the process, so entries are scoped by API URL and
+// app id: the same installation id may exist on another GitHub Enterprise
+// server, and a different app has different permissions
+#[derive(Default)]
+struct TokenCache {
+    // (scope, "owner/repo") -> installation id
+    installations: HashMap<(String, String), u64>,
+    // (scope, installation id) -> token
+    tokens: HashMap<(String, u64), InstallationToken>,
+}
+
+fn cache() -> &'static Mutex<TokenCache> {
+    static CACHE: OnceLock<Mutex<TokenCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(TokenCache::default()))
+}
+
+// Credentials of a GitHub App, used to mint installation tokens
+pub struct AppCredentials {
+    app_id: String,
+    private_key: Vec<u8>,
+}
+
+impl AppCredentials {
+    pub fn new(app_id: String, private_key_pem: Vec<u8>) -> Self {
+        AppCredentials { app_id, private_key: private_key_pem }
+    }
+
+    // Read GITHUB_APP_ID together with either GITHUB_APP_PRIVATE_KEY (PEM contents)
+    // or GITHUB_APP_PRIVATE_KEY_PATH. Returns None if no app is configured.
+    pub fn from_env() -> Result<Option<Self>, ReqError> {
+        let app_id = match env::var("GITHUB_APP_ID") {
+            Ok(val) => val,
+            Err(_err) => return Ok(None),
+        };
+        let private_key = match env::var("GITHUB_APP_PRIVATE_KEY") {
+            Ok(pem) => pem.into_bytes(),
+            Err(_err) => match env::var("GITHUB_APP_PRIVATE_KEY_PATH") {
+                Ok(path) => fs::read(&path).map_err(|err| {
+                    ReqError::InvalidAppConfig(format!("failed to read GITHUB_APP_PRIVATE_KEY_PATH {}: {}", path, err))
+                })?,
+                Err(_err) => {
+                    return Err(ReqError::InvalidAppConfig(
+                        "GITHUB_APP_ID is set but neither GITHUB_APP_PRIVATE_KEY nor GITHUB_APP_PRIVATE_KEY_PATH is".to_string(),
+                    ))
+                }
+            },
+        };
+        Ok(Some(AppCredentials::new(app_id, private_key)))
+    }
+
+    // Sign a short-lived RS256 JWT identifying the app
+    fn jwt(&self) -> Result<String, ReqError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            // allow for clock drift between us and GitHub
+            iat: now - 60,
+            exp: now + JWT_LIFETIME_SECS,
+            iss: self.app_id.clone(),
+        };
+        let key = EncodingKey::from_rsa_pem(&self.private_key)?;
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &key)?;
+        Ok(token)
+    }
+
+    // Return an installation token for the repository, reusing a cached one until
+    // shortly before it expires
+    pub async fn installation_token(&self, client: &GitHubClient, owner: &str, repo: &str) -> Result<String, ReqError> {
+        Ok(self.installation(client, owner, repo).await?.token)
+    }
+
+    // The permissions the installation's token has, e.g. "contents" -> "write"
+    pub async fn installation_permissions(&self, client: &GitHubClient, owner: &str, repo: &str) -> Result<HashMap<String, String>, ReqError> {
+        Ok(self.installation(client, owner, repo).await?.permissions)
+    }
+
+    async fn installation(&self, client: &GitHubClient, owner: &str, repo: &str) -> Result<InstallationToken, ReqError> {
+        let scope = format!("{} app {}", api_url(), self.app_id);
+        let key = (scope.clone(), format!("{}/{}", owner, repo));
+
+        // the cache lock is never held across a request
+        let cached = cache().lock().unwrap().installations.get(&key).copied();
+        let installation_id = match cached {
+            Some(id) => id,
+            None => {
+                let id = self.installation_id(client, owner, repo).await?;
+                cache().lock().unwrap().installations.insert(key, id);
+                id
+            }
+        };
+
+        let key = (scope, installation_id);
+        if let Some(token) = cache().lock().unwrap().tokens.get(&key) {
+            if token.is_fresh() {
+                return Ok(token.clone());
+            }
+        }
+
+        let token = self.create_installation_token(client, installation_id).await?;
+        cache().lock().unwrap().tokens.insert(key, token.clone());
+        Ok(token)
+    }
+
+    // Login of the bot account the app acts as, e.g. "my-app[bot]"
+    pub async fn bot_login(&self, client: &GitHubClient) -> Result<String, ReqError> {
+        let url = format!("{}/app", api_url());
+        let request = client.http
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.jwt()?))
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(client.timeouts.for_operation("get_app"));
+        let response = client.send(request).await?;
+        eprintln!("get_app HTTP code {:?}", response.status());
+        let data: App = check_response(response)?.json().await?;
+        Ok(format!("{}[bot]", data.slug))
+    }
+
+    // Look up the app installation that covers the repository
+    async fn installation_id(&self, client: &GitHubClient, owner: &str, repo: &str) -> Result<u64, ReqError> {
+        let url = format!("{}/repos/{}/{}/installation", api_url(), owner, repo);
+        let request = client.http
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.jwt()?))
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(client.timeouts.for_operation("get_installation"));
+        let response = client.send(request).await?;
+        eprintln!("get_installation HTTP code {:?}", response.status());
+        // Without an installation there is no token to read or write the repository
+        // with; reported here rather than as the NotFound of whatever request needed it
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(ReqError::AppNotInstalled(format!("{}/{}", owner, repo)));
+        }
+        let data: Installation = check_response(response)?.json().await?;
+        Ok(data.id)
+    }
+
+    // Exchange the app JWT for an installation access token
+    async fn create_installation_token(&self, client: &GitHubClient, installation_id: u64) -> Result<InstallationToken, ReqError> {
+        let url = format!("{}/app/installations/{}/access_tokens", api_url(), installation_id);
+        let request = client.http
+            .post(url)
+            .header("Authorization", format!("Bearer {}", self.jwt()?))
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(client.timeouts.for_operation("create_installation_token"));
+        // A retried request at worst mints a second token, which is harmless
+        let response = client.send_replayable(request).await?;
+        eprintln!("create_installation_token HTTP code {:?}", response.status());
+        let response = match check_response(response) {
+            Ok(response) => response,
+            Err(ReqError::AuthError) => return Err(ReqError::AuthenticationTimeoutFailure),
+            Err(err) => return Err(err),
+        };
+        let data: InstallationToken = response.json().await?;
+        Ok(data)
+    }
+}
@@ -0,0 +1,264 @@
+// A blocking client for callers that don't run an async runtime. Every method
+// drives the corresponding async GitHubClient method to completion on a private
+// single-threaded tokio runtime, so both clients share one implementation.
+// Like reqwest::blocking, it must not be used from within an async context.
+use crate::api_client::*;
+use crate::cache::HttpCache;
+use crate::client;
+use crate::credentials::CredentialChain;
+use crate::error::ReqError;
+use crate::pagination::PageOptions;
+use crate::rate_limit::RateLimit;
+use crate::retry::RetryPolicy;
+use crate::timeouts::Timeouts;
+use crate::update::{Update, UpdateOutcome, UpdatePlan};
+use futures::{Stream, StreamExt};
+use std::pin::Pin;
+use std::time::Duration;
+use tokio::runtime::{Builder, Runtime};
+
+pub struct GitHubClient {
+    inner: client::GitHubClient,
+    runtime: Runtime,
+}
+
+// Iterator over the items of a list endpoint; each page is fetched when the
+// items of the previous one have been consumed
+pub struct Paginated<'a, T> {
+    runtime: &'a Runtime,
+    stream: Pin<Box<dyn Stream<Item = Result<T, ReqError>> + 'a>>,
+}
+
+impl<'a, T> Iterator for Paginated<'a, T> {
+    type Item = Result<T, ReqError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
+
+impl Default for GitHubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitHubClient {
+    pub fn new() -> Self {
+        let runtime = Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("failed to start tokio runtime");
+        GitHubClient {
+            inner: client::GitHubClient::new(),
+            runtime,
+        }
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.inner = self.inner.with_timeouts(timeouts);
+        self
+    }
+
+    pub fn with_user_agent(mut self, app: &str) -> Self {
+        self.inner = self.inner.with_user_agent(app);
+        self
+    }
+
+    pub fn with_operation_timeout(mut self, operation: &str, timeout: Duration) -> Self {
+        self.inner = self.inner.with_operation_timeout(operation, timeout);
+        self
+    }
+
+    pub fn with_upload_concurrency(mut self, upload_concurrency: usize) -> Self {
+        self.inner = self.inner.with_upload_concurrency(upload_concurrency);
+        self
+    }
+
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.inner = self.inner.with_cache(cache);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.inner = self.inner.with_retry_policy(retry);
+        self
+    }
+
+    pub fn with_credentials(mut self, credentials: CredentialChain) -> Self {
+        self.inner = self.inner.with_credentials(credentials);
+        self
+    }
+
+    fn paginated<'a, T>(&'a self, stream: impl Stream<Item = Result<T, ReqError>> + 'a) -> Paginated<'a, T> {
+        Paginated {
+            runtime: &self.runtime,
+            stream: Box::pin(stream),
+        }
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.inner.rate_limit()
+    }
+
+    pub fn fetch_rate_limit(&self, owner: &str, repo: &str) -> Result<RateLimit, ReqError> {
+        self.runtime.block_on(self.inner.fetch_rate_limit(owner, repo))
+    }
+
+    pub fn preflight(&self, owner: &str, repo: &str) -> Result<Repository, ReqError> {
+        self.runtime.block_on(self.inner.preflight(owner, repo))
+    }
+
+    pub fn get_base(&self, owner: &str, repo: &str, base_ref: &str) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.get_base(owner, repo, base_ref))
+    }
+
+    pub fn create_fork(&self, owner: &str, repo: &str, organization: Option<&str>) -> Result<Repository, ReqError> {
+        self.runtime.block_on(self.inner.create_fork(owner, repo, organization))
+    }
+
+    pub fn wait_for_fork(&self, owner: &str, repo: &str, branch: &str) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.wait_for_fork(owner, repo, branch))
+    }
+
+    pub fn sync_fork(&self, owner: &str, repo: &str, upstream_owner: &str, upstream_repo: &str, branch: &str) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.sync_fork(owner, repo, upstream_owner, upstream_repo, branch))
+    }
+
+    pub fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison, ReqError> {
+        self.runtime.block_on(self.inner.compare_commits(owner, repo, base, head))
+    }
+
+    pub fn bootstrap_repository(&self, owner: &str, repo: &str, branch: &str, files: &[TreeFile]) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.bootstrap_repository(owner, repo, branch, files))
+    }
+
+    pub fn get_ref(&self, owner: &str, repo: &str, gitref: String, head_sha: String) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.get_ref(owner, repo, gitref, head_sha))
+    }
+
+    pub fn find_ref(&self, owner: &str, repo: &str, gitref: &str) -> Result<Option<Content>, ReqError> {
+        self.runtime.block_on(self.inner.find_ref(owner, repo, gitref))
+    }
+
+    pub fn list_refs<'a>(&'a self, owner: &'a str, repo: &'a str, prefix: &str, options: PageOptions) -> Paginated<'a, Content> {
+        self.paginated(self.inner.list_refs(owner, repo, prefix, options))
+    }
+
+    pub fn create_tree(&self, owner: &str, repo: &str, head_sha: String, files: &[TreeFile]) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.create_tree(owner, repo, head_sha, files))
+    }
+
+    pub fn upload_blobs(&self, owner: &str, repo: &str, files: &[TreeFile]) -> Result<Vec<NewTreeEntry>, ReqError> {
+        self.runtime.block_on(self.inner.upload_blobs(owner, repo, files))
+    }
+
+    pub fn blob_exists(&self, owner: &str, repo: &str, sha: &str) -> Result<bool, ReqError> {
+        self.runtime.block_on(self.inner.blob_exists(owner, repo, sha))
+    }
+
+    pub fn create_blob(&self, owner: &str, repo: &str, content: &[u8]) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.create_blob(owner, repo, content))
+    }
+
+    pub fn get_blob(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<u8>, ReqError> {
+        self.runtime.block_on(self.inner.get_blob(owner, repo, sha))
+    }
+
+    pub fn get_tree(&self, owner: &str, repo: &str, sha: &str, recursive: bool) -> Result<Vec<TreeObject>, ReqError> {
+        self.runtime.block_on(self.inner.get_tree(owner, repo, sha, recursive))
+    }
+
+    pub fn get_file(&self, owner: &str, repo: &str, path: &str, git_ref: Option<&str>) -> Result<Vec<u8>, ReqError> {
+        self.runtime.block_on(self.inner.get_file(owner, repo, path, git_ref))
+    }
+
+    pub fn get_tree_recursive(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<TreeObject>, ReqError> {
+        self.runtime.block_on(self.inner.get_tree_recursive(owner, repo, sha))
+    }
+
+    pub fn get_parent_commit(&self, owner: &str, repo: &str, head_sha: String) -> Result<Commit, ReqError> {
+        self.runtime.block_on(self.inner.get_parent_commit(owner, repo, head_sha))
+    }
+
+    pub fn list_commits<'a>(&'a self, owner: &'a str, repo: &'a str, sha: Option<&str>, options: PageOptions) -> Paginated<'a, RepoCommit> {
+        self.paginated(self.inner.list_commits(owner, repo, sha, options))
+    }
+
+    pub fn push_commit(&self, current_ref: String, owner: &str, repo: &str, tree_sha: String, base_sha: String) -> Result<String, ReqError> {
+        self.runtime.block_on(self.inner.push_commit(current_ref, owner, repo, tree_sha, base_sha))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_pr(&self, owner: &str, repo: &str, title: &str, head: String, base: String, maintainer_can_modify: bool, draft: bool) -> Result<PullRequest, ReqError> {
+        self.runtime.block_on(self.inner.create_pr(owner, repo, title, head, base, maintainer_can_modify, draft))
+    }
+
+    pub fn list_pull_requests<'a>(&'a self, owner: &'a str, repo: &'a str, state: &str, options: PageOptions) -> Paginated<'a, PullRequest> {
+        self.paginated(self.inner.list_pull_requests(owner, repo, state, options))
+    }
+
+    pub fn list_pull_request_files<'a>(&'a self, owner: &'a str, repo: &'a str, pr_number: u64, options: PageOptions) -> Paginated<'a, PullRequestFile> {
+        self.paginated(self.inner.list_pull_request_files(owner, repo, pr_number, options))
+    }
+
+    pub fn create_status(&self, owner: &str, repo: &str, sha: &str, status: &NewStatus) -> Result<Status, ReqError> {
+        self.runtime.block_on(self.inner.create_status(owner, repo, sha, status))
+    }
+
+    pub fn create_check_run(&self, owner: &str, repo: &str, check_run: &NewCheckRun) -> Result<CheckRun, ReqError> {
+        self.runtime.block_on(self.inner.create_check_run(owner, repo, check_run))
+    }
+
+    pub fn list_check_runs<'a>(&'a self, owner: &'a str, repo: &'a str, git_ref: &str, options: PageOptions) -> Paginated<'a, CheckRun> {
+        self.paginated(self.inner.list_check_runs(owner, repo, git_ref, options))
+    }
+
+    pub fn report_verification(&self, owner: &str, repo: &str, sha: &str, results: &[ArtifactVerification]) -> Result<CheckRun, ReqError> {
+        self.runtime.block_on(self.inner.report_verification(owner, repo, sha, results))
+    }
+
+    pub fn list_comments(&self, owner: &str, repo: &str, issue_number: u64) -> Result<Vec<IssueComment>, ReqError> {
+        self.runtime.block_on(self.inner.list_comments(owner, repo, issue_number))
+    }
+
+    pub fn create_comment(&self, owner: &str, repo: &str, issue_number: u64, body: &str) -> Result<IssueComment, ReqError> {
+        self.runtime.block_on(self.inner.create_comment(owner, repo, issue_number, body))
+    }
+
+    pub fn update_comment(&self, owner: &str, repo: &str, comment_id: i64, body: &str) -> Result<IssueComment, ReqError> {
+        self.runtime.block_on(self.inner.update_comment(owner, repo, comment_id, body))
+    }
+
+    pub fn delete_comment(&self, owner: &str, repo: &str, comment_id: i64) -> Result<(), ReqError> {
+        self.runtime.block_on(self.inner.delete_comment(owner, repo, comment_id))
+    }
+
+    pub fn get_authenticated_user(&self, owner: &str, repo: &str) -> Result<User, ReqError> {
+        self.runtime.block_on(self.inner.get_authenticated_user(owner, repo))
+    }
+
+    pub fn upsert_sticky_comment(&self, owner: &str, repo: &str, issue_number: u64, marker: &str, body: &str) -> Result<IssueComment, ReqError> {
+        self.runtime.block_on(self.inner.upsert_sticky_comment(owner, repo, issue_number, marker, body))
+    }
+
+    pub fn create_review(&self, owner: &str, repo: &str, pr_number: u64, review: &NewReview) -> Result<Review, ReqError> {
+        self.runtime.block_on(self.inner.create_review(owner, repo, pr_number, review))
+    }
+
+    pub fn review_verification(&self, owner: &str, repo: &str, pr_number: u64, commit_id: &str, results: &[ArtifactVerification]) -> Result<Review, ReqError> {
+        self.runtime.block_on(self.inner.review_verification(owner, repo, pr_number, commit_id, results))
+    }
+
+    pub fn apply_update(&self, update: &Update) -> Result<UpdateOutcome, ReqError> {
+        self.runtime.block_on(self.inner.apply_update(update))
+    }
+
+    pub fn plan_update(&self, update: &Update) -> Result<UpdatePlan, ReqError> {
+        self.runtime.block_on(self.inner.plan_update(update))
+    }
+
+    pub fn apply_updates(&self, updates: &[Update], concurrency: usize) -> Vec<Result<UpdateOutcome, ReqError>> {
+        self.runtime.block_on(self.inner.apply_updates(updates, concurrency))
+    }
+}
@@ -0,0 +1,79 @@
+use crate::error::ReqError;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+// A response body stored for revalidation with If-None-Match / If-Modified-Since
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CacheEntry {
+    pub url: String,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+    // Link header of a list page, needed to find the next page
+    #[serde(default)]
+    pub link: Option<String>,
+    pub stored_at: DateTime<Utc>,
+    pub body: String,
+}
+
+// On-disk cache of GET responses. Entries older than the TTL are discarded
+// instead of being revalidated.
+#[derive(Debug, Clone)]
+pub struct HttpCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+impl HttpCache {
+    pub fn new(dir: PathBuf, ttl: Duration) -> Self {
+        HttpCache { dir, ttl }
+    }
+
+    // $XDG_CACHE_HOME/sget-github, falling back to ~/.cache/sget-github
+    pub fn default_dir() -> PathBuf {
+        let base = match env::var_os("XDG_CACHE_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".cache"))
+                .unwrap_or_else(env::temp_dir),
+        };
+        base.join("sget-github")
+    }
+
+    // Entries are keyed by URL and by the credentials used, so that responses
+    // for a private repository are never served to a different token
+    fn path(&self, url: &str, auth: Option<&str>) -> PathBuf {
+        let mut hasher = Sha1::new();
+        hasher.update(url.as_bytes());
+        hasher.update(b"\0");
+        hasher.update(auth.unwrap_or("").as_bytes());
+        let key: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn get(&self, url: &str, auth: Option<&str>) -> Option<CacheEntry> {
+        let path = self.path(url, auth);
+        let contents = fs::read_to_string(&path).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+        let ttl = ChronoDuration::from_std(self.ttl).unwrap_or_else(|_| ChronoDuration::max_value());
+        if entry.url != url || Utc::now() - entry.stored_at > ttl {
+            let _ = fs::remove_file(&path);
+            return None;
+        }
+        Some(entry)
+    }
+
+    pub fn put(&self, auth: Option<&str>, entry: &CacheEntry) -> Result<(), ReqError> {
+        fs::create_dir_all(&self.dir)?;
+        let path = self.path(&entry.url, auth);
+        // write to a temporary file first so concurrent readers never see a partial entry
+        let tmp = path.with_extension(format!("tmp{}", std::process::id()));
+        fs::write(&tmp, serde_json::to_vec(entry)?)?;
+        fs::rename(tmp, path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1487 @@
+use crate::api_client::*;
+use crate::auth::AppCredentials;
+use crate::cache::{CacheEntry, HttpCache};
+use crate::credentials::{credential_host, CredentialChain};
+use crate::error::ReqError;
+use crate::pagination::{self, Page, PageOptions, MAX_PER_PAGE};
+use crate::rate_limit::{self, RateLimit, RateLimitOverview};
+use crate::retry::{self, RetryPolicy};
+use crate::timeouts::Timeouts;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Utc;
+use futures::stream::{self, Stream, StreamExt, TryStreamExt};
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use url::Url;
+
+// Base URL of the REST API, overridable with GITHUB_API_URL for GitHub Enterprise
+pub fn api_url() -> String {
+    match env::var("GITHUB_API_URL") {
+        Ok(val) if !val.trim().is_empty() => val.trim().trim_end_matches('/').to_string(),
+        _ => "https://api.github.com".to_string(),
+    }
+}
+
+// Sent with every request so GitHub and proxies can attribute traffic to this tool
+pub const USER_AGENT: &str = concat!("sget-github/", env!("CARGO_PKG_VERSION"));
+
+// Messages of the commits made by push_commit and bootstrap_repository
+pub const COMMIT_MESSAGE: &str = "Update script";
+pub const INITIAL_COMMIT_MESSAGE: &str = "Initial commit";
+
+const DEFAULT_UPLOAD_CONCURRENCY: usize = 8;
+
+// How many times a request rejected by a rate limit is retried before giving up
+const MAX_THROTTLE_RETRIES: u32 = 3;
+
+// Permissions an app installation needs to push a branch and open a pull request
+const REQUIRED_APP_PERMISSIONS: &[&str] = &["contents", "pull_requests"];
+
+// GitHub creates forks in the background, which can take a few minutes
+const FORK_POLL_INTERVAL: Duration = Duration::from_secs(5);
+const FORK_POLL_ATTEMPTS: u32 = 60;
+
+pub struct GitHubClient {
+    pub(crate) http: reqwest::Client,
+    user_agent: String,
+    retry: RetryPolicy,
+    pub(crate) timeouts: Timeouts,
+    // how many blobs are uploaded at once
+    upload_concurrency: usize,
+    cache: Option<HttpCache>,
+    // GitHub App from the environment, read once; a bad configuration is kept
+    // as its message and reported by every request that needs credentials
+    app: Result<Option<AppCredentials>, String>,
+    credentials: Arc<CredentialChain>,
+    // tokens found by the credential chain, per host; providers may shell out
+    // to git, so each host is only looked up once
+    tokens: Mutex<HashMap<String, Option<String>>>,
+    // budget reported by the most recent response
+    rate_limit: Mutex<Option<RateLimit>>,
+}
+
+fn build_http_client(timeouts: &Timeouts, user_agent: &str) -> reqwest::Client {
+    reqwest::Client::builder()
+        .user_agent(user_agent)
+        // every request sets its own total timeout
+        .connect_timeout(timeouts.connect)
+        .build()
+        .expect("failed to initialise HTTP client")
+}
+
+impl Default for GitHubClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GitHubClient {
+    pub fn new() -> Self {
+        let timeouts = Timeouts::default();
+        let user_agent = USER_AGENT.to_string();
+        GitHubClient {
+            http: build_http_client(&timeouts, &user_agent),
+            user_agent,
+            retry: RetryPolicy::default(),
+            timeouts,
+            upload_concurrency: DEFAULT_UPLOAD_CONCURRENCY,
+            cache: None,
+            app: AppCredentials::from_env().map_err(|err| match err {
+                ReqError::InvalidAppConfig(message) => message,
+                err => err.to_string(),
+            }),
+            credentials: Arc::new(CredentialChain::default()),
+            tokens: Mutex::new(HashMap::new()),
+            rate_limit: Mutex::new(None),
+        }
+    }
+
+    pub fn with_timeouts(mut self, timeouts: Timeouts) -> Self {
+        self.http = build_http_client(&timeouts, &self.user_agent);
+        self.timeouts = timeouts;
+        self
+    }
+
+    // Append an application identifier to the User-Agent, e.g. "my-bot/1.2"
+    // gives "sget-github/0.1.0 my-bot/1.2"
+    pub fn with_user_agent(mut self, app: &str) -> Self {
+        self.user_agent = format!("{} {}", USER_AGENT, app);
+        self.http = build_http_client(&self.timeouts, &self.user_agent);
+        self
+    }
+
+    // Override the total timeout of a single operation, e.g. "create_tree"
+    pub fn with_operation_timeout(mut self, operation: &str, timeout: Duration) -> Self {
+        self.timeouts.operations.insert(operation.to_string(), timeout);
+        self
+    }
+
+    pub fn with_upload_concurrency(mut self, upload_concurrency: usize) -> Self {
+        self.upload_concurrency = upload_concurrency.max(1);
+        self
+    }
+
+    // Cache read responses on disk and revalidate them with conditional requests
+    pub fn with_cache(mut self, cache: HttpCache) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    // Look tokens up with the given providers instead of the default chain
+    pub fn with_credentials(mut self, credentials: CredentialChain) -> Self {
+        self.credentials = Arc::new(credentials);
+        self.tokens = Mutex::new(HashMap::new());
+        self
+    }
+
+    // The GitHub App configured in the environment, if any
+    fn app(&self) -> Result<Option<&AppCredentials>, ReqError> {
+        match &self.app {
+            Ok(app) => Ok(app.as_ref()),
+            Err(message) => Err(ReqError::InvalidAppConfig(message.clone())),
+        }
+    }
+
+    // Use a GitHub App installation token when an app is configured, otherwise ask
+    // the credential provider chain for a token for the API host. Returns None when
+    // no credentials are configured at all.
+    async fn resolve_github_token(&self, owner: &str, repo: &str) -> Result<Option<String>, ReqError> {
+        if let Some(app) = self.app()? {
+            let token = app.installation_token(self, owner, repo).await?;
+            return Ok(Some(format!("Token {}", token)));
+        }
+
+        let host = credential_host(&api_url());
+        // the lock is never held across the lookup, which may shell out to git
+        let cached = self.tokens.lock().unwrap().get(&host).cloned();
+        let token = match cached {
+            Some(token) => token,
+            None => {
+                let credentials = self.credentials.clone();
+                let lookup_host = host.clone();
+                let token = run_blocking(move || credentials.token(&lookup_host)).await?;
+                self.tokens.lock().unwrap().insert(host, token.clone());
+                token
+            }
+        };
+        Ok(token.map(|token| format!("Token {}", token)))
+    }
+
+    // Authorization header value for requests that need credentials
+    async fn get_github_token(&self, owner: &str, repo: &str) -> Result<String, ReqError> {
+        self.resolve_github_token(owner, repo).await?.ok_or(ReqError::MissingCredentials)
+    }
+
+    // Fail early, before anything is mutated, if no credentials are configured
+    pub async fn require_credentials(&self, owner: &str, repo: &str) -> Result<(), ReqError> {
+        self.get_github_token(owner, repo).await.map(|_| ())
+    }
+
+    // Attach credentials to a read-only request if any are configured. Reads of public
+    // repositories are allowed anonymously, so no Authorization header is sent otherwise.
+    async fn optional_auth(&self, request: reqwest::RequestBuilder, owner: &str, repo: &str) -> Result<reqwest::RequestBuilder, ReqError> {
+        match self.resolve_github_token(owner, repo).await? {
+            Some(token) => Ok(request.header("Authorization", token)),
+            None => Ok(request),
+        }
+    }
+
+    // Remaining request budget as of the last response, if any request was made yet
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        *self.rate_limit.lock().unwrap()
+    }
+
+    // Ask GitHub for the current budget. Calls to this endpoint are not counted
+    // against the rate limit.
+    pub async fn fetch_rate_limit(&self, owner: &str, repo: &str) -> Result<RateLimit, ReqError> {
+        let url = format!("{}/rate_limit", api_url());
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("fetch_rate_limit"));
+        let response = self.send(request).await?;
+        let data: RateLimitOverview = check_response(response)?.json().await?;
+        *self.rate_limit.lock().unwrap() = Some(data.rate);
+        Ok(data.rate)
+    }
+
+    // Send a request, waiting out an exhausted budget beforehand, retrying requests
+    // that were rejected by a primary or secondary rate limit, and retrying
+    // transient failures according to the retry policy. Only idempotent methods
+    // are replayed after a failure that may have reached GitHub.
+    pub(crate) async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ReqError> {
+        let replayable = request
+            .try_clone()
+            .and_then(|r| r.build().ok())
+            .is_some_and(|r| retry::is_idempotent(r.method()));
+        self.send_with(request, replayable).await
+    }
+
+    // Like send, but for POSTs that are safe to replay, such as creating
+    // content-addressed git objects or requests whose duplicates the caller reconciles
+    pub(crate) async fn send_replayable(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, ReqError> {
+        self.send_with(request, true).await
+    }
+
+    async fn send_with(&self, request: reqwest::RequestBuilder, replayable: bool) -> Result<reqwest::Response, ReqError> {
+        let mut request = request;
+        let mut throttle_attempt = 0;
+        let mut retry_attempt = 0;
+        loop {
+            let exhausted = self.rate_limit().and_then(|limit| limit.wait_time());
+            if let Some(wait) = exhausted {
+                eprintln!("rate limit exhausted, waiting {}s for reset", wait.as_secs());
+                tokio::time::sleep(wait).await;
+            }
+
+            let next = request.try_clone();
+            let retries_left = retry_attempt + 1 < self.retry.max_attempts;
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    request = match next {
+                        Some(next) if retries_left && retry::is_retryable_error(&err, replayable) => next,
+                        _ => return Err(err.into()),
+                    };
+                    let wait = self.retry.backoff(retry_attempt);
+                    eprintln!("request failed ({}), retrying in {}ms", err, wait.as_millis());
+                    tokio::time::sleep(wait).await;
+                    retry_attempt += 1;
+                    continue;
+                }
+            };
+            if let Some(limit) = RateLimit::from_headers(response.headers()) {
+                *self.rate_limit.lock().unwrap() = Some(limit);
+            }
+
+            let mut throttle = rate_limit::throttled(response.status(), response.headers(), throttle_attempt);
+            let response = if throttle.is_none() && response.status() == reqwest::StatusCode::FORBIDDEN {
+                let (response, secondary) = inspect_forbidden(response).await?;
+                if secondary {
+                    throttle = Some(rate_limit::secondary_backoff(throttle_attempt));
+                }
+                response
+            } else {
+                response
+            };
+            if let Some(wait) = throttle {
+                request = match next {
+                    Some(next) if throttle_attempt < MAX_THROTTLE_RETRIES => next,
+                    _ => return Err(ReqError::TooManyRequest),
+                };
+                eprintln!("rate limited, retrying in {}s", wait.as_secs());
+                tokio::time::sleep(wait).await;
+                throttle_attempt += 1;
+                continue;
+            }
+
+            // reqwest only bounds a whole request, so the body is read here to
+            // give up on a response that stops sending data
+            let response = match read_body(response, self.timeouts.read).await {
+                Ok(response) => response,
+                Err(err @ ReqError::ReadTimeout(_)) => {
+                    request = match next {
+                        Some(next) if replayable && retries_left => next,
+                        _ => return Err(err),
+                    };
+                    let wait = self.retry.backoff(retry_attempt);
+                    eprintln!("{}, retrying in {}ms", err, wait.as_millis());
+                    tokio::time::sleep(wait).await;
+                    retry_attempt += 1;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+
+            if retry::is_transient_status(response.status()) && replayable && retries_left {
+                if let Some(next) = next {
+                    let wait = self.retry.backoff(retry_attempt);
+                    eprintln!("HTTP {}, retrying in {}ms", response.status(), wait.as_millis());
+                    tokio::time::sleep(wait).await;
+                    request = next;
+                    retry_attempt += 1;
+                    continue;
+                }
+            }
+            return Ok(response);
+        }
+    }
+
+    // Send a GET and parse the JSON response. With a cache configured, the request
+    // is made conditional on the cached ETag / Last-Modified, and on 304 Not
+    // Modified (which doesn't count against the rate limit) the cached body is reused.
+    async fn get_json<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder, operation: &str) -> Result<T, ReqError> {
+        let (data, _link) = self.fetch_json(request, operation).await?;
+        Ok(data)
+    }
+
+    // Like get_json, but also returns the Link header used for pagination
+    async fn fetch_json<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder, operation: &str) -> Result<(T, Option<String>), ReqError> {
+        let header = |response: &reqwest::Response, name: &str| {
+            response.headers().get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string())
+        };
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => {
+                let response = self.send(request).await?;
+                eprintln!("{} HTTP code {:?}", operation, response.status());
+                let response = check_response(response)?;
+                let link = header(&response, "Link");
+                return Ok((response.json().await?, link));
+            }
+        };
+
+        let built = request.try_clone().and_then(|r| r.build().ok());
+        let (url, auth) = match &built {
+            Some(built) => (
+                built.url().to_string(),
+                built.headers().get("Authorization").and_then(|v| v.to_str().ok()).map(|v| v.to_string()),
+            ),
+            None => return Err(ReqError::BadRequest),
+        };
+
+        let cached = {
+            let (cache, url, auth) = (cache.clone(), url.clone(), auth.clone());
+            run_blocking(move || cache.get(&url, auth.as_deref())).await
+        };
+        let mut request = request;
+        if let Some(entry) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header("If-None-Match", etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header("If-Modified-Since", last_modified);
+            }
+        }
+
+        let response = self.send(request).await?;
+        eprintln!("{} HTTP code {:?}", operation, response.status());
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some(entry) = cached {
+                return Ok((serde_json::from_str(&entry.body)?, entry.link));
+            }
+        }
+
+        let response = check_response(response)?;
+        let etag = header(&response, "ETag");
+        let last_modified = header(&response, "Last-Modified");
+        let link = header(&response, "Link");
+        let body = response.text().await?;
+        let data = serde_json::from_str(&body)?;
+        if etag.is_some() || last_modified.is_some() {
+            let entry = CacheEntry {
+                url,
+                etag,
+                last_modified,
+                link: link.clone(),
+                stored_at: Utc::now(),
+                body,
+            };
+            let cache = cache.clone();
+            run_blocking(move || cache.put(auth.as_deref(), &entry)).await?;
+        }
+        Ok((data, link))
+    }
+
+    // Stream the items of a list endpoint, following the Link: rel="next" header
+    // from page to page. Pages are only fetched as the stream is polled, so
+    // max_items (or dropping the stream) stops requests for further pages.
+    pub fn paginate<'a, T: DeserializeOwned + 'a>(&'a self, owner: &'a str, repo: &'a str, url: String, operation: &'a str, options: PageOptions) -> impl Stream<Item = Result<T, ReqError>> + 'a {
+        let per_page = options.per_page.clamp(1, MAX_PER_PAGE).to_string();
+        // an unparseable URL is passed through so the request reports the error
+        let first = match Url::parse(&url) {
+            Ok(mut url) => {
+                url.query_pairs_mut().append_pair("per_page", &per_page);
+                url.to_string()
+            }
+            Err(_err) => url,
+        };
+        let pages = stream::try_unfold(Some(first), move |next| async move {
+            let url = match next {
+                Some(url) => url,
+                None => return Ok(None),
+            };
+            let request = self.optional_auth(self.http.get(url), owner, repo).await?
+                .header("Accept", "application/vnd.github.v3+json")
+                .timeout(self.timeouts.for_operation(operation));
+            let (page, link): (Page<T>, Option<String>) = self.fetch_json(request, operation).await?;
+            let next = link.as_deref().and_then(pagination::next_link);
+            let items = page.into_items().into_iter().map(Ok);
+            Ok::<_, ReqError>(Some((stream::iter(items), next)))
+        });
+        let items = pages.try_flatten();
+        match options.max_items {
+            Some(max_items) => items.take(max_items).left_stream(),
+            None => items.right_stream(),
+        }
+    }
+
+    // Check, before anything is mutated, that the token can push branches and open
+    // pull requests on the repository. Classic tokens report their scopes in the
+    // X-OAuth-Scopes header; the repository's permissions reflect the access the
+    // token's user (or app installation) has been granted.
+    pub async fn preflight(&self, owner: &str, repo: &str) -> Result<Repository, ReqError> {
+        self.require_credentials(owner, repo).await?;
+        let url = format!("{}/repos/{}/{}", api_url(), owner, repo);
+        let request = self.http
+            .get(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("preflight"));
+        let response = self.send(request).await?;
+        eprintln!("preflight HTTP code {:?}", response.status());
+        let scopes = response
+            .headers()
+            .get("X-OAuth-Scopes")
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect::<Vec<String>>());
+        let data: Repository = check_response(response)?.json().await?;
+
+        // an empty header lists no scopes to check; the repository permissions
+        // below still apply
+        if let Some(scopes) = scopes.filter(|scopes| !scopes.is_empty()) {
+            let public = data.private == Some(false);
+            let has_scope = scopes.iter().any(|s| s == "repo") || (public && scopes.iter().any(|s| s == "public_repo"));
+            if !has_scope {
+                let needed = if public { "token scope `repo` or `public_repo`" } else { "token scope `repo`" };
+                return Err(ReqError::MissingPermission(format!("{} (token has: {})", needed, scopes.join(", "))));
+            }
+        }
+
+        // An app installation token carries its own permissions, which may be
+        // narrower than the repository access it reports
+        if let Some(app) = self.app()? {
+            let granted = app.installation_permissions(self, owner, repo).await?;
+            let missing: Vec<String> = REQUIRED_APP_PERMISSIONS
+                .iter()
+                .filter(|permission| granted.get(**permission).map(String::as_str) != Some("write"))
+                .map(|permission| format!("`{}:write`", permission))
+                .collect();
+            if !missing.is_empty() {
+                return Err(ReqError::MissingPermission(format!(
+                    "{} on {}/{} for the GitHub App installation",
+                    missing.join(" and "),
+                    owner,
+                    repo
+                )));
+            }
+        }
+
+        // push access covers contents; a fine-grained token without pull_requests
+        // access is reported from X-Accepted-GitHub-Permissions by check_response
+        // when the pull request is opened
+        if let Some(permissions) = &data.permissions {
+            if !(permissions.push || permissions.maintain || permissions.admin) {
+                return Err(ReqError::MissingPermission(format!("`contents:write` on {}/{}", owner, repo)));
+            }
+        }
+
+        if data.archived == Some(true) {
+            return Err(ReqError::MissingPermission(format!("{}/{} is archived and read-only", owner, repo)));
+        }
+
+        Ok(data)
+    }
+
+    // Get the head SHA of the repository (required for get_ref). Uses the
+    // exact-match ref endpoint, since git/refs/heads/<name> lists every branch
+    // starting with <name> when there is no exact match.
+    pub async fn get_base(&self, owner: &str, repo: &str, base_ref: &str) -> Result<String, ReqError> {
+        let url = format!("{}/repos/{}/{}/git/ref/heads/{}", api_url(), owner, repo, base_ref);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_base"));
+        match self.get_json::<Content>(request, "get_base").await {
+            Ok(data) => Ok(data.object.sha),
+            Err(ReqError::NotFound) => Err(ReqError::MissingBranch(base_ref.to_string())),
+            // GitHub answers 409 "Git Repository is empty" for repositories without commits
+            Err(ReqError::Conflict) => Err(ReqError::EmptyRepository),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Fork a repository into the authenticated user's account, or into an
+    // organization. If the fork already exists GitHub returns it instead.
+    pub async fn create_fork(&self, owner: &str, repo: &str, organization: Option<&str>) -> Result<Repository, ReqError> {
+        let url = format!("{}/repos/{}/{}/forks", api_url(), owner, repo);
+        let body = NewFork { organization };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_fork"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_fork HTTP code {:?}", response.status());
+        let data: Repository = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Wait until a newly created fork has its copy of branch and return the SHA
+    // the branch points at
+    pub async fn wait_for_fork(&self, owner: &str, repo: &str, branch: &str) -> Result<String, ReqError> {
+        for attempt in 0..FORK_POLL_ATTEMPTS {
+            match self.get_base(owner, repo, branch).await {
+                Ok(sha) => return Ok(sha),
+                // the fork or its refs don't exist yet
+                Err(ReqError::NotFound) | Err(ReqError::MissingBranch(_)) | Err(ReqError::EmptyRepository) => {
+                    eprintln!("waiting for fork {}/{} (attempt {})", owner, repo, attempt + 1);
+                    tokio::time::sleep(FORK_POLL_INTERVAL).await;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(ReqError::ForkNotReady(format!("{}/{}", owner, repo)))
+    }
+
+    // Bring a fork's branch up to date with the same branch upstream and return
+    // the SHA it ends up at. Uses GitHub's merge-upstream endpoint, and where
+    // that isn't available (older GitHub Enterprise) or fails, fast-forwards the
+    // fork's ref directly if the fork hasn't diverged.
+    pub async fn sync_fork(&self, owner: &str, repo: &str, upstream_owner: &str, upstream_repo: &str, branch: &str) -> Result<String, ReqError> {
+        let url = format!("{}/repos/{}/{}/merge-upstream", api_url(), owner, repo);
+        let body = UpstreamBranch { branch };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("sync_fork"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("sync_fork HTTP code {:?}", response.status());
+        match check_response(response) {
+            Ok(response) => {
+                let data: MergeUpstream = response.json().await?;
+                eprintln!("merge-upstream: {:?}", data.merge_type);
+                return self.get_base(owner, repo, branch).await;
+            }
+            Err(ReqError::NotFound) | Err(ReqError::Conflict) | Err(ReqError::BadRequest) => {}
+            Err(err) => return Err(err),
+        }
+
+        let upstream_sha = self.get_base(upstream_owner, upstream_repo, branch).await?;
+        let fork_sha = self.get_base(owner, repo, branch).await?;
+        if upstream_sha == fork_sha {
+            return Ok(fork_sha);
+        }
+        let comparison = self.compare_commits(upstream_owner, upstream_repo, &fork_sha, &upstream_sha).await?;
+        match comparison.status.as_str() {
+            "identical" => Ok(fork_sha),
+            "ahead" => {
+                self.update_ref(owner, repo, format!("refs/heads/{}", branch), upstream_sha.clone(), false).await?;
+                Ok(upstream_sha)
+            }
+            _ => Err(ReqError::ForkDiverged(format!("{}/{}:{}", owner, repo, branch))),
+        }
+    }
+
+    // Compare two commits. The status describes head relative to base: "ahead"
+    // means base is an ancestor of head.
+    pub async fn compare_commits(&self, owner: &str, repo: &str, base: &str, head: &str) -> Result<Comparison, ReqError> {
+        let url = format!("{}/repos/{}/{}/compare/{}...{}", api_url(), owner, repo, base, head);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("compare_commits"));
+        let data: Comparison = self.get_json(request, "compare_commits").await?;
+        Ok(data)
+    }
+
+    // Give an empty repository its first commit, containing the files, on the
+    // given branch (which becomes the default branch). The Git Data API refuses
+    // to create blobs or trees until the repository has a commit, so a
+    // placeholder file is written through the Contents API first, then replaced
+    // by a parentless commit of the real files.
+    pub async fn bootstrap_repository(&self, owner: &str, repo: &str, branch: &str, files: &[TreeFile]) -> Result<String, ReqError> {
+        if files.is_empty() {
+            return Err(ReqError::BadRequest);
+        }
+        let url = format!("{}/repos/{}/{}/contents/.sget-github-init", api_url(), owner, repo);
+        let body = NewContentFile {
+            message: "Initialize repository",
+            content: String::new(),
+            branch,
+        };
+        let request = self.http
+            .put(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("bootstrap_repository"))
+            .json(&body);
+        let response = self.send(request).await?;
+        eprintln!("bootstrap_repository HTTP code {:?}", response.status());
+        check_response(response)?;
+
+        let tree_sha = self.build_tree(owner, repo, None, files).await?;
+        let commit = self.create_commit(owner, repo, INITIAL_COMMIT_MESSAGE, &tree_sha, &[]).await?;
+        self.update_ref(owner, repo, format!("refs/heads/{}", branch), commit.sha.clone(), true).await?;
+        Ok(commit.sha)
+    }
+
+    // Check if named ref exists, if not (404 not found) create it
+    pub async fn get_ref(&self, owner: &str, repo: &str, gitref: String, head_sha: String) -> Result<String, ReqError> {
+        let url = ref_url(owner, repo, &gitref);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_ref"));
+        let response = self.send(request).await?;
+
+        match response.status() {
+            reqwest::StatusCode::NOT_FOUND => self.create_ref(owner, repo, gitref, head_sha).await,
+            // the ref is already taken
+            status if status.is_success() => Err(ReqError::Conflict),
+            _ => check_response(response).and(Err(ReqError::BadRequest)),
+        }
+    }
+
+    // Create a ref
+    async fn create_ref(&self, owner: &str, repo: &str, gitref: String, head_sha: String) -> Result<String, ReqError> {
+        eprintln!("Creating ref: {}", gitref);
+        let url: String = format!("{}/repos/{}/{}/git/refs", api_url(), owner, repo);
+        let body = format!(
+            r#"{{
+                "ref": "{}",
+                "sha": "{}"
+            }}"#,
+            gitref, head_sha
+        );
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_ref"))
+            .body(body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_ref HTTP code {:?}", response.status());
+
+        // A retried request may already have created the ref; accept it if it
+        // points at the commit we asked for
+        if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+            let existing = self.fetch_ref(owner, repo, &gitref).await?;
+            if existing.object.sha == head_sha {
+                return Ok(existing.ref_);
+            }
+            // "Reference already exists", pointing somewhere else
+            return Err(ReqError::Conflict);
+        }
+        let data: Content = check_response(response)?.json().await?;
+        let ref_name = data.ref_;
+        Ok(ref_name)
+    }
+
+    // Read a ref such as refs/heads/main
+    async fn fetch_ref(&self, owner: &str, repo: &str, gitref: &str) -> Result<Content, ReqError> {
+        let url = ref_url(owner, repo, gitref);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("fetch_ref"));
+        let data: Content = self.get_json(request, "fetch_ref").await?;
+        Ok(data)
+    }
+
+    // Look up a ref such as refs/heads/main without creating it
+    pub async fn find_ref(&self, owner: &str, repo: &str, gitref: &str) -> Result<Option<Content>, ReqError> {
+        match self.fetch_ref(owner, repo, gitref).await {
+            Ok(data) => Ok(Some(data)),
+            Err(ReqError::NotFound) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    // List refs starting with a prefix such as "heads/" or "tags/v1"
+    pub fn list_refs<'a>(&'a self, owner: &'a str, repo: &'a str, prefix: &str, options: PageOptions) -> impl Stream<Item = Result<Content, ReqError>> + 'a {
+        let url = format!("{}/repos/{}/{}/git/matching-refs/{}", api_url(), owner, repo, prefix);
+        self.paginate(owner, repo, url, "list_refs", options)
+    }
+
+    // Point a ref at another commit. Without force the update must be a fast-forward.
+    async fn update_ref(&self, owner: &str, repo: &str, current_ref: String, new_sha: String, force: bool) -> Result<String, ReqError> {
+        let url: String = format!("{}/repos/{}/{}/git/{}", api_url(), owner, repo, current_ref);
+        let body = format!(
+            r#"{{
+                "sha": "{}",
+                "force": {}
+            }}"#, new_sha, force
+        );
+        let request = self.http
+            .patch(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("update_ref"))
+            .body(body);
+        let response = self.send(request).await?;
+        eprintln!("update_ref HTTP code {:?}", response.status());
+        // "Update is not a fast forward"
+        if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY && !force {
+            return Err(ReqError::Conflict);
+        }
+        let data: Content = check_response(response)?.json().await?;
+        let ref_name = data.ref_;
+        Ok(ref_name)
+    }
+
+    // Upload the files as blobs and create a git tree containing them on top of base_tree
+    pub async fn create_tree(&self, owner: &str, repo: &str, head_sha: String, files: &[TreeFile]) -> Result<String, ReqError> {
+        self.build_tree(owner, repo, Some(&head_sha), files).await
+    }
+
+    // Without a base tree the new tree contains only the files
+    async fn build_tree(&self, owner: &str, repo: &str, base_tree: Option<&str>, files: &[TreeFile]) -> Result<String, ReqError> {
+        let entries = self.upload_blobs(owner, repo, files).await?;
+        let url: String = format!("{}/repos/{}/{}/git/trees", api_url(), owner, repo);
+        let body = NewTree {
+            base_tree,
+            tree: &entries,
+        };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_tree"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+
+        eprintln!("Create tree: {:?}", response.status());
+        let data: TreeEntry = check_response(response)?.json().await?;
+        let tree_sha = data.sha;
+        Ok(tree_sha)
+    }
+
+    // Upload the contents of every file as a blob, at most upload_concurrency at a
+    // time, and return the tree entries referring to them. Blobs that already exist
+    // in the repository (by their locally computed SHA) are not uploaded again.
+    pub async fn upload_blobs(&self, owner: &str, repo: &str, files: &[TreeFile]) -> Result<Vec<NewTreeEntry>, ReqError> {
+        // Collected first: a closure held inside the stream would make this
+        // future !Send (rust-lang/rust#102211)
+        let uploads: Vec<_> = files.iter().map(|file| self.upload_blob(owner, repo, file)).collect();
+        stream::iter(uploads)
+            .buffered(self.upload_concurrency)
+            .try_collect()
+            .await
+    }
+
+    // Upload one file unless the repository already has a blob with its contents
+    async fn upload_blob(&self, owner: &str, repo: &str, file: &TreeFile) -> Result<NewTreeEntry, ReqError> {
+        let sha = blob_sha(&file.content);
+        let sha = if self.blob_exists(owner, repo, &sha).await? {
+            eprintln!("blob for {} already exists, skipping upload", file.path);
+            sha
+        } else {
+            self.create_blob(owner, repo, &file.content).await?
+        };
+        Ok(NewTreeEntry {
+            path: file.path.clone(),
+            mode: file.mode.clone(),
+            type_: "blob".to_string(),
+            sha,
+        })
+    }
+
+    // Check whether a blob exists without downloading it
+    pub async fn blob_exists(&self, owner: &str, repo: &str, sha: &str) -> Result<bool, ReqError> {
+        let url = format!("{}/repos/{}/{}/git/blobs/{}", api_url(), owner, repo, sha);
+        let request = self.optional_auth(self.http.head(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("blob_exists"));
+        let response = self.send(request).await?;
+        match check_response(response) {
+            Ok(_) => Ok(true),
+            Err(ReqError::NotFound) => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Upload a blob and return its SHA
+    pub async fn create_blob(&self, owner: &str, repo: &str, content: &[u8]) -> Result<String, ReqError> {
+        let url = format!("{}/repos/{}/{}/git/blobs", api_url(), owner, repo);
+        let body = NewBlob {
+            content: BASE64.encode(content),
+            encoding: "base64",
+        };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_blob"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_blob HTTP code {:?}", response.status());
+        let data: BlobRef = check_response(response)?.json().await?;
+        Ok(data.sha)
+    }
+
+    // Download a blob's contents
+    pub async fn get_blob(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<u8>, ReqError> {
+        let url = format!("{}/repos/{}/{}/git/blobs/{}", api_url(), owner, repo, sha);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_blob"));
+        let data: Blob = self.get_json(request, "get_blob").await?;
+        decode_content(&data.content, &data.encoding)
+    }
+
+    // Read a single tree object, optionally with all of its subtrees flattened in
+    async fn fetch_tree(&self, owner: &str, repo: &str, sha: &str, recursive: bool) -> Result<TreeEntry, ReqError> {
+        let mut url = format!("{}/repos/{}/{}/git/trees/{}", api_url(), owner, repo, sha);
+        if recursive {
+            url.push_str("?recursive=1");
+        }
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("fetch_tree"));
+        let data: TreeEntry = self.get_json(request, "fetch_tree").await?;
+        Ok(data)
+    }
+
+    // List every entry below a tree, with paths relative to it. GitHub truncates
+    // recursive listings of very large trees, so when that happens the tree is
+    // read one level at a time instead, again trying a recursive listing for each
+    // subtree and only descending further into the ones that are still too large.
+    pub async fn get_tree_recursive(&self, owner: &str, repo: &str, sha: &str) -> Result<Vec<TreeObject>, ReqError> {
+        let mut entries = Vec::new();
+        let mut pending = VecDeque::from([(String::new(), sha.to_string())]);
+        while let Some((prefix, sha)) = pending.pop_front() {
+            let tree = self.fetch_tree(owner, repo, &sha, true).await?;
+            let tree = if tree.truncated {
+                eprintln!("tree listing of {:?} truncated, walking its subtrees", prefix);
+                let tree = self.fetch_tree(owner, repo, &sha, false).await?;
+                if tree.truncated {
+                    // a single directory with more entries than GitHub will list
+                    return Err(ReqError::TruncatedTree(sha));
+                }
+                for entry in &tree.tree {
+                    if entry.type_ == "tree" {
+                        pending.push_back((join_path(&prefix, &entry.path), entry.sha.clone()));
+                    }
+                }
+                tree
+            } else {
+                tree
+            };
+            entries.extend(tree.tree.into_iter().map(|mut entry| {
+                entry.path = join_path(&prefix, &entry.path);
+                entry
+            }));
+        }
+        Ok(entries)
+    }
+
+    // List a tree's entries, or every entry below it when recursive
+    pub async fn get_tree(&self, owner: &str, repo: &str, sha: &str, recursive: bool) -> Result<Vec<TreeObject>, ReqError> {
+        if recursive {
+            return self.get_tree_recursive(owner, repo, sha).await;
+        }
+        let tree = self.fetch_tree(owner, repo, sha, false).await?;
+        if tree.truncated {
+            return Err(ReqError::TruncatedTree(sha.to_string()));
+        }
+        Ok(tree.tree)
+    }
+
+    // Read a file through the Contents API, at a branch, tag or SHA (the default
+    // branch if None). Files too large for the Contents API are read as blobs.
+    pub async fn get_file(&self, owner: &str, repo: &str, path: &str, git_ref: Option<&str>) -> Result<Vec<u8>, ReqError> {
+        let mut url = Url::parse(&format!("{}/repos/{}/{}/contents", api_url(), owner, repo))
+            .map_err(|_err| ReqError::BadRequest)?;
+        url.path_segments_mut()
+            .map_err(|_err| ReqError::BadRequest)?
+            .extend(path.split('/').filter(|segment| !segment.is_empty()));
+        if let Some(git_ref) = git_ref {
+            url.query_pairs_mut().append_pair("ref", git_ref);
+        }
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_file"));
+        let file = match self.get_json(request, "get_file").await? {
+            Contents::File(file) if file.type_ == "file" => file,
+            _ => return Err(ReqError::NotAFile(path.to_string())),
+        };
+        match (file.content, file.encoding) {
+            (Some(content), Some(encoding)) if encoding != "none" => decode_content(&content, &encoding),
+            _ => self.get_blob(owner, repo, &file.sha).await,
+        }
+    }
+
+    pub async fn get_parent_commit(&self, owner: &str, repo: &str, head_sha: String) -> Result<Commit, ReqError> {
+         let url: String = format!("{}/repos/{}/{}/git/commits/{}", api_url(), owner, repo, head_sha);
+         let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_parent_commit"));
+        let data: Commit = self.get_json(request, "get_commit").await?;
+        Ok(data)
+    }
+
+    // List commits reachable from a branch, tag or SHA (the default branch if None)
+    pub fn list_commits<'a>(&'a self, owner: &'a str, repo: &'a str, sha: Option<&str>, options: PageOptions) -> impl Stream<Item = Result<RepoCommit, ReqError>> + 'a {
+        let mut url = format!("{}/repos/{}/{}/commits", api_url(), owner, repo);
+        if let Some(sha) = sha {
+            url = format!("{}?sha={}", url, sha);
+        }
+        self.paginate(owner, repo, url, "list_commits", options)
+    }
+
+    // Create a commit object without moving any ref to it
+    async fn create_commit(&self, owner: &str, repo: &str, message: &str, tree_sha: &str, parents: &[String]) -> Result<Commit, ReqError> {
+        let url = format!("{}/repos/{}/{}/git/commits", api_url(), owner, repo);
+        let body = NewCommit {
+            message,
+            tree: tree_sha,
+            parents,
+        };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_commit"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_commit HTTP code: {:?}", response.status());
+        let data: Commit = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Commit the tree as a child of base_sha and move current_ref to it
+    pub async fn push_commit(&self, current_ref: String, owner: &str, repo: &str, tree_sha: String, base_sha: String) -> Result<String, ReqError> {
+        let commit = self.create_commit(owner, repo, COMMIT_MESSAGE, &tree_sha, &[base_sha]).await?;
+
+        // Attach the commit to the branch that was recently created
+        self.update_ref(owner, repo, current_ref, commit.sha.clone(), true).await?;
+        Ok(commit.sha)
+    }
+
+    // push the pull request
+    #[allow(clippy::too_many_arguments)]
+    pub async fn create_pr(&self, owner: &str, repo: &str,  title: &str, head: String, base: String, maintainer_can_modify: bool, draft: bool)  -> Result<PullRequest, ReqError> {
+        let url: String = format!("{}/repos/{}/{}/pulls", api_url(), owner, repo);
+        let body = NewPullRequest {
+            title: title.to_string(),
+            head,
+            base,
+            maintainer_can_modify,
+            draft,
+        };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_pr"))
+            .json(&body);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_pr HTTP code {:?}", response.status());
+
+        // GitHub refuses a second pull request for the same head and base, which
+        // is what a retried request that already succeeded runs into
+        if response.status() == reqwest::StatusCode::UNPROCESSABLE_ENTITY {
+            let message = response.text().await?;
+            return match self.find_open_pr(owner, repo, &body.head, &body.base).await? {
+                Some(pr) => Ok(pr),
+                // "A pull request already exists", but not an open one we could find
+                None if message.contains("already exists") => Err(ReqError::Conflict),
+                None => Err(ReqError::BadRequest),
+            };
+        }
+        let data: PullRequest = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Find the open pull request from `head` into `base`, if there is one
+    async fn find_open_pr(&self, owner: &str, repo: &str, head: &str, base: &str) -> Result<Option<PullRequest>, ReqError> {
+        // head must be qualified with the owner of the repository it lives in
+        let head = if head.contains(':') { head.to_string() } else { format!("{}:{}", owner, head) };
+        let url = format!("{}/repos/{}/{}/pulls", api_url(), owner, repo);
+        let request = self.optional_auth(self.http.get(url), owner, repo).await?
+            .query(&[("head", head.as_str()), ("base", base), ("state", "open")])
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("find_open_pr"));
+        let data: Vec<PullRequest> = self.get_json(request, "find_open_pr").await?;
+        Ok(data.into_iter().next())
+    }
+
+    // List pull requests in the given state ("open", "closed" or "all")
+    pub fn list_pull_requests<'a>(&'a self, owner: &'a str, repo: &'a str, state: &str, options: PageOptions) -> impl Stream<Item = Result<PullRequest, ReqError>> + 'a {
+        let url = format!("{}/repos/{}/{}/pulls?state={}", api_url(), owner, repo, state);
+        self.paginate(owner, repo, url, "list_pull_requests", options)
+    }
+
+    // List the files a pull request changes
+    pub fn list_pull_request_files<'a>(&'a self, owner: &'a str, repo: &'a str, pr_number: u64, options: PageOptions) -> impl Stream<Item = Result<PullRequestFile, ReqError>> + 'a {
+        let url = format!("{}/repos/{}/{}/pulls/{}/files", api_url(), owner, repo, pr_number);
+        self.paginate(owner, repo, url, "list_pull_request_files", options)
+    }
+}
+
+// GitHub accepts at most 50 annotations per check run request
+const MAX_ANNOTATIONS: usize = 50;
+
+impl GitHubClient {
+    // Set a commit status on the given SHA
+    pub async fn create_status(&self, owner: &str, repo: &str, sha: &str, status: &NewStatus) -> Result<Status, ReqError> {
+        let url = format!("{}/repos/{}/{}/statuses/{}", api_url(), owner, repo, sha);
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_status"))
+            .json(status);
+        let response = self.send_replayable(request).await?;
+        eprintln!("create_status HTTP code {:?}", response.status());
+        let data: Status = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Create a check run, sending any annotations beyond the first 50 as follow-up updates
+    pub async fn create_check_run(&self, owner: &str, repo: &str, check_run: &NewCheckRun) -> Result<CheckRun, ReqError> {
+        let url = format!("{}/repos/{}/{}/check-runs", api_url(), owner, repo);
+        let (first, rest) = match &check_run.output {
+            Some(output) if output.annotations.len() > MAX_ANNOTATIONS => {
+                let mut first = output.clone();
+                let rest = first.annotations.split_off(MAX_ANNOTATIONS);
+                (Some(first), rest)
+            }
+            Some(output) => (Some(output.clone()), Vec::new()),
+            None => (None, Vec::new()),
+        };
+        let body = NewCheckRun {
+            name: check_run.name.clone(),
+            head_sha: check_run.head_sha.clone(),
+            status: check_run.status,
+            conclusion: check_run.conclusion,
+            details_url: check_run.details_url.clone(),
+            output: first.clone(),
+        };
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_check_run"))
+            .json(&body);
+        let response = self.send(request).await?;
+        eprintln!("create_check_run HTTP code {:?}", response.status());
+        let data: CheckRun = check_response(response)?.json().await?;
+
+        if let Some(first) = first {
+            for chunk in rest.chunks(MAX_ANNOTATIONS) {
+                let output = CheckRunOutput {
+                    annotations: chunk.to_vec(),
+                    ..first.clone()
+                };
+                self.update_check_run_output(owner, repo, data.id, &output).await?;
+            }
+        }
+        Ok(data)
+    }
+
+    // Append output (and annotations) to an existing check run
+    async fn update_check_run_output(&self, owner: &str, repo: &str, check_run_id: i64, output: &CheckRunOutput) -> Result<CheckRun, ReqError> {
+        let url = format!("{}/repos/{}/{}/check-runs/{}", api_url(), owner, repo, check_run_id);
+        let body = serde_json::json!({ "output": output });
+        let request = self.http
+            .patch(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("update_check_run_output"))
+            .json(&body);
+        let response = self.send(request).await?;
+        eprintln!("update_check_run HTTP code {:?}", response.status());
+        let data: CheckRun = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // List the check runs for a commit SHA, branch or tag
+    pub fn list_check_runs<'a>(&'a self, owner: &'a str, repo: &'a str, git_ref: &str, options: PageOptions) -> impl Stream<Item = Result<CheckRun, ReqError>> + 'a {
+        let url = format!("{}/repos/{}/{}/commits/{}/check-runs", api_url(), owner, repo, git_ref);
+        self.paginate(owner, repo, url, "list_check_runs", options)
+    }
+
+    // Report sget verification results on a commit as both a commit status and a check run
+    pub async fn report_verification(&self, owner: &str, repo: &str, sha: &str, results: &[ArtifactVerification]) -> Result<CheckRun, ReqError> {
+        let failed = results.iter().filter(|r| !r.verified).count();
+        let passed = results.len() - failed;
+        let summary = format!("{} of {} artifacts verified", passed, results.len());
+
+        let (state, conclusion) = if failed == 0 {
+            (StatusState::Success, CheckRunConclusion::Success)
+        } else {
+            (StatusState::Failure, CheckRunConclusion::Failure)
+        };
+
+        self.create_status(owner, repo, sha, &NewStatus {
+            state,
+            target_url: None,
+            description: Some(summary.clone()),
+            context: "sget/verify".to_string(),
+        }).await?;
+
+        let mut text = String::from("| Artifact | Result |\n|---|---|\n");
+        for r in results {
+            let result = if r.verified { "verified" } else { "FAILED" };
+            text.push_str(&format!("| `{}` | {} |\n", r.path, result));
+        }
+
+        let annotations = results
+            .iter()
+            .filter(|r| !r.verified)
+            .map(|r| Annotation {
+                path: r.path.clone(),
+                start_line: 1,
+                end_line: 1,
+                annotation_level: AnnotationLevel::Failure,
+                message: r.message.clone(),
+                title: Some("Signature verification failed".to_string()),
+            })
+            .collect();
+
+        self.create_check_run(owner, repo, &NewCheckRun {
+            name: "sget verify".to_string(),
+            head_sha: sha.to_string(),
+            status: CheckRunStatus::Completed,
+            conclusion: Some(conclusion),
+            details_url: None,
+            output: Some(CheckRunOutput {
+                title: summary.clone(),
+                summary,
+                text: Some(text),
+                annotations,
+            }),
+        }).await
+    }
+}
+
+impl GitHubClient {
+    // List all comments on an issue or pull request
+    pub async fn list_comments(&self, owner: &str, repo: &str, issue_number: u64) -> Result<Vec<IssueComment>, ReqError> {
+        let url = format!("{}/repos/{}/{}/issues/{}/comments", api_url(), owner, repo, issue_number);
+        self.paginate(owner, repo, url, "list_comments", PageOptions::default())
+            .try_collect()
+            .await
+    }
+
+    // Post a new comment on an issue or pull request
+    pub async fn create_comment(&self, owner: &str, repo: &str, issue_number: u64, body: &str) -> Result<IssueComment, ReqError> {
+        let url = format!("{}/repos/{}/{}/issues/{}/comments", api_url(), owner, repo, issue_number);
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_comment"))
+            .json(&CommentBody { body });
+        let response = self.send(request).await?;
+        eprintln!("create_comment HTTP code {:?}", response.status());
+        let data: IssueComment = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Replace the body of an existing comment
+    pub async fn update_comment(&self, owner: &str, repo: &str, comment_id: i64, body: &str) -> Result<IssueComment, ReqError> {
+        let url = format!("{}/repos/{}/{}/issues/comments/{}", api_url(), owner, repo, comment_id);
+        let request = self.http
+            .patch(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("update_comment"))
+            .json(&CommentBody { body });
+        let response = self.send(request).await?;
+        eprintln!("update_comment HTTP code {:?}", response.status());
+        let data: IssueComment = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Delete a comment
+    pub async fn delete_comment(&self, owner: &str, repo: &str, comment_id: i64) -> Result<(), ReqError> {
+        let url = format!("{}/repos/{}/{}/issues/comments/{}", api_url(), owner, repo, comment_id);
+        let request = self.http
+            .delete(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("delete_comment"));
+        let response = self.send(request).await?;
+        eprintln!("delete_comment HTTP code {:?}", response.status());
+        check_response(response)?;
+        Ok(())
+    }
+
+    // The user the credentials belong to
+    pub async fn get_authenticated_user(&self, owner: &str, repo: &str) -> Result<User, ReqError> {
+        let url = format!("{}/user", api_url());
+        let request = self.http
+            .get(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("get_authenticated_user"));
+        let response = self.send(request).await?;
+        eprintln!("get_authenticated_user HTTP code {:?}", response.status());
+        let data: User = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Login that comments made with the credentials are shown under. Installation
+    // tokens can't read /user; their comments are posted by "<app slug>[bot]".
+    async fn comment_author(&self, owner: &str, repo: &str) -> Result<String, ReqError> {
+        match self.app()? {
+            Some(app) => app.bot_login(self).await,
+            None => Ok(self.get_authenticated_user(owner, repo).await?.login),
+        }
+    }
+
+    // Create or edit a single "sticky" comment identified by a hidden marker, so that
+    // repeated runs update the same report instead of adding a new comment each time
+    pub async fn upsert_sticky_comment(&self, owner: &str, repo: &str, issue_number: u64, marker: &str, body: &str) -> Result<IssueComment, ReqError> {
+        let tag = format!("<!-- {} -->", marker);
+        let body = format!("{}\n{}", tag, body);
+        // Anyone can paste the marker into a comment; only our own can be edited
+        let login = self.comment_author(owner, repo).await?;
+        let existing = self.list_comments(owner, repo, issue_number).await?
+            .into_iter()
+            .filter(|c| c.user.as_ref().is_some_and(|user| user.login == login))
+            .find(|c| c.body.as_deref().is_some_and(|b| b.contains(&tag)));
+
+        match existing {
+            Some(comment) => self.update_comment(owner, repo, comment.id, &body).await,
+            None => self.create_comment(owner, repo, issue_number, &body).await,
+        }
+    }
+}
+
+impl GitHubClient {
+    // Submit a review on a pull request, optionally with inline comments
+    pub async fn create_review(&self, owner: &str, repo: &str, pr_number: u64, review: &NewReview) -> Result<Review, ReqError> {
+        let url = format!("{}/repos/{}/{}/pulls/{}/reviews", api_url(), owner, repo, pr_number);
+        let request = self.http
+            .post(url)
+            .header("Authorization", self.get_github_token(owner, repo).await?)
+            .header("Accept", "application/vnd.github.v3+json")
+            .timeout(self.timeouts.for_operation("create_review"))
+            .json(review);
+        let response = self.send(request).await?;
+        eprintln!("create_review HTTP code {:?}", response.status());
+        let data: Review = check_response(response)?.json().await?;
+        Ok(data)
+    }
+
+    // Review a pull request based on sget verification results: request changes if
+    // any artifact failed verification, otherwise approve. Failures are commented
+    // inline when the artifact is part of the pull request's diff; GitHub rejects
+    // the whole review for comments on lines outside it, so failures for other
+    // artifacts (and binary files, which have no diff lines) are listed in the
+    // review body instead.
+    pub async fn review_verification(&self, owner: &str, repo: &str, pr_number: u64, commit_id: &str, results: &[ArtifactVerification]) -> Result<Review, ReqError> {
+        let failed: Vec<&ArtifactVerification> = results.iter().filter(|r| !r.verified).collect();
+
+        let mut diff_lines = HashMap::new();
+        if !failed.is_empty() {
+            let files: Vec<PullRequestFile> = self
+                .list_pull_request_files(owner, repo, pr_number, PageOptions::default())
+                .try_collect()
+                .await?;
+            for file in files {
+                if let Some(line) = file.patch.as_deref().and_then(first_diff_line) {
+                    diff_lines.insert(file.filename, line);
+                }
+            }
+        }
+
+        let mut comments = Vec::new();
+        let mut outside_diff = Vec::new();
+        for r in &failed {
+            let body = format!("Signature verification failed: {}", r.message);
+            match diff_lines.get(&r.path) {
+                Some(&line) => comments.push(ReviewComment {
+                    path: r.path.clone(),
+                    body,
+                    line,
+                    side: Some(DiffSide::Right),
+                    start_line: None,
+                    start_side: None,
+                }),
+                None => outside_diff.push(format!("- `{}`: {}", r.path, body)),
+            }
+        }
+
+        let (event, mut body) = if results.is_empty() {
+            // nothing was verified, which is no reason to approve
+            (ReviewEvent::Comment, "No artifacts were verified.".to_string())
+        } else if failed.is_empty() {
+            (ReviewEvent::Approve, format!("All {} artifacts verified.", results.len()))
+        } else {
+            (ReviewEvent::RequestChanges, format!("{} of {} artifacts failed verification.", failed.len(), results.len()))
+        };
+        if !outside_diff.is_empty() {
+            body = format!("{}\n\n{}", body, outside_diff.join("\n"));
+        }
+
+        self.create_review(owner, repo, pr_number, &NewReview {
+            commit_id: Some(commit_id.to_string()),
+            body: Some(body),
+            event,
+            comments,
+        }).await
+    }
+}
+
+// Read a 403's body to tell a secondary rate limit from a permission error.
+// The body is consumed, so the response is rebuilt around it for the caller.
+async fn inspect_forbidden(response: reqwest::Response) -> Result<(reqwest::Response, bool), ReqError> {
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let body = response.bytes().await?;
+    let secondary = rate_limit::is_secondary_limit(&body);
+    Ok((rebuild_response(status, version, headers, body.to_vec()), secondary))
+}
+
+// Read a whole response body, failing if no part of it arrives within `idle`.
+// The response is rebuilt around the body for the caller.
+async fn read_body(response: reqwest::Response, idle: Duration) -> Result<reqwest::Response, ReqError> {
+    let status = response.status();
+    let version = response.version();
+    let headers = response.headers().clone();
+    let mut response = response;
+    let mut body = Vec::new();
+    loop {
+        match tokio::time::timeout(idle, response.chunk()).await {
+            Ok(chunk) => match chunk? {
+                Some(chunk) => body.extend_from_slice(&chunk),
+                None => break,
+            },
+            Err(_elapsed) => return Err(ReqError::ReadTimeout(idle)),
+        }
+    }
+    Ok(rebuild_response(status, version, headers, body))
+}
+
+fn rebuild_response(status: reqwest::StatusCode, version: reqwest::Version, headers: reqwest::header::HeaderMap, body: Vec<u8>) -> reqwest::Response {
+    let mut rebuilt = http::Response::new(body);
+    *rebuilt.status_mut() = status;
+    *rebuilt.version_mut() = version;
+    *rebuilt.headers_mut() = headers;
+    reqwest::Response::from(rebuilt)
+}
+
+// Exact-match URL of a ref such as refs/heads/main. The git/refs/<name> form
+// answers with every ref starting with <name> when there is no exact match.
+fn ref_url(owner: &str, repo: &str, gitref: &str) -> String {
+    let name = gitref.strip_prefix("refs/").unwrap_or(gitref);
+    format!("{}/repos/{}/{}/git/ref/{}", api_url(), owner, repo, name)
+}
+
+// Run file system access and child processes on tokio's blocking pool rather
+// than on the thread polling the request. A panic in `work` is passed on as if
+// it had run inline.
+async fn run_blocking<T: Send + 'static>(work: impl FnOnce() -> T + Send + 'static) -> T {
+    match tokio::task::spawn_blocking(work).await {
+        Ok(value) => value,
+        Err(err) => std::panic::resume_unwind(err.into_panic()),
+    }
+}
+
+// Map the HTTP status of a response onto a ReqError
+// https://docs.rs/reqwest/latest/reqwest/struct.StatusCode.html
+pub(crate) fn check_response(response: reqwest::Response) -> Result<reqwest::Response, ReqError> {
+    match response.status() {
+        status if status.is_success() => Ok(response),
+        reqwest::StatusCode::BAD_REQUEST => Err(ReqError::BadRequest),
+        reqwest::StatusCode::UNPROCESSABLE_ENTITY => Err(ReqError::BadRequest),
+        reqwest::StatusCode::UNAUTHORIZED => Err(ReqError::AuthError),
+        // Fine-grained tokens and app tokens are told which permissions the
+        // request needed, e.g. "pull_requests=write"
+        reqwest::StatusCode::FORBIDDEN => match response.headers().get("X-Accepted-GitHub-Permissions") {
+            Some(accepted) => Err(ReqError::MissingPermission(accepted_permissions(accepted.to_str().unwrap_or_default()))),
+            None => Err(ReqError::AuthError),
+        },
+        reqwest::StatusCode::NOT_FOUND => Err(ReqError::NotFound),
+        reqwest::StatusCode::CONFLICT => Err(ReqError::Conflict),
+        reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE => Err(ReqError::UnsupportedMediaType),
+        reqwest::StatusCode::TOO_MANY_REQUESTS => Err(ReqError::TooManyRequest),
+        _ => Err(ReqError::UnknownConnectionError),
+    }
+}
+
+// "contents=write; pull_requests=write" -> "`contents:write` or `pull_requests:write`".
+// The header separates alternative sets with ';' and the permissions within a
+// set with ','.
+fn accepted_permissions(header: &str) -> String {
+    header
+        .split(';')
+        .map(|set| {
+            set.split(',')
+                .map(|permission| format!("`{}`", permission.trim().replacen('=', ":", 1)))
+                .collect::<Vec<_>>()
+                .join(" and ")
+        })
+        .collect::<Vec<_>>()
+        .join(" or ")
+}
+
+// The first line of a file's patch that exists on the new (RIGHT) side, from the
+// "@@ -a,b +c,d @@" header of its first hunk that adds or keeps any lines
+fn first_diff_line(patch: &str) -> Option<u32> {
+    patch
+        .lines()
+        .filter(|line| line.starts_with("@@"))
+        .filter_map(|header| {
+            let new_range = header.split_whitespace().find(|part| part.starts_with('+'))?;
+            let mut range = new_range[1..].split(',');
+            let start: u32 = range.next()?.parse().ok()?;
+            let count: u32 = match range.next() {
+                Some(count) => count.parse().ok()?,
+                None => 1,
+            };
+            Some((start, count))
+        })
+        .find(|&(start, count)| start > 0 && count > 0)
+        .map(|(start, _)| start)
+}
+
+fn join_path(prefix: &str, path: &str) -> String {
+    if prefix.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}/{}", prefix, path)
+    }
+}
+
+// Blob and Contents API responses are base64 with a line break every 60 characters
+fn decode_content(content: &str, encoding: &str) -> Result<Vec<u8>, ReqError> {
+    match encoding {
+        "base64" => {
+            let content: String = content.split_whitespace().collect();
+            Ok(BASE64.decode(content)?)
+        }
+        _ => Ok(content.as_bytes().to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_diff_line_of_patches() {
+        assert_eq!(first_diff_line("@@ -0,0 +1,3 @@\n+a\n+b\n+c"), Some(1));
+        assert_eq!(first_diff_line("@@ -10,6 +12,7 @@ fn main() {\n context"), Some(12));
+        assert_eq!(first_diff_line("@@ -1 +1 @@\n-a\n+b"), Some(1));
+        // a deleted file has no lines on the new side
+        assert_eq!(first_diff_line("@@ -1,2 +0,0 @@\n-a\n-b"), None);
+        assert_eq!(first_diff_line(""), None);
+    }
+
+    #[test]
+    fn accepted_permissions_header() {
+        assert_eq!(accepted_permissions("pull_requests=write"), "`pull_requests:write`");
+        assert_eq!(
+            accepted_permissions("contents=write,pull_requests=write"),
+            "`contents:write` and `pull_requests:write`"
+        );
+        assert_eq!(
+            accepted_permissions("contents=read; metadata=read"),
+            "`contents:read` or `metadata:read`"
+        );
+    }
+}
@@ -0,0 +1,210 @@
+use crate::error::ReqError;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use url::Url;
+
+// A source of personal/OAuth tokens, queried per host (e.g. "github.com" or
+// "github.example.com" for GitHub Enterprise)
+pub trait CredentialProvider {
+    fn name(&self) -> &'static str;
+    fn token(&self, host: &str) -> Result<Option<String>, ReqError>;
+}
+
+// Tokens for GitHub.com and for Enterprise hosts come from separate variables,
+// as with gh's GH_TOKEN and GH_ENTERPRISE_TOKEN, so a GitHub.com token is never
+// sent to an Enterprise server (or the other way round)
+fn is_github_com(host: &str) -> bool {
+    host == "github.com"
+}
+
+// GITHUB_AUTH_TOKEN for github.com, GITHUB_ENTERPRISE_AUTH_TOKEN for any other host
+pub struct EnvProvider;
+
+impl CredentialProvider for EnvProvider {
+    fn name(&self) -> &'static str {
+        "environment"
+    }
+
+    fn token(&self, host: &str) -> Result<Option<String>, ReqError> {
+        let var = if is_github_com(host) { "GITHUB_AUTH_TOKEN" } else { "GITHUB_ENTERPRISE_AUTH_TOKEN" };
+        match env::var(var) {
+            Ok(val) if !val.trim().is_empty() => Ok(Some(val.trim().to_string())),
+            _ => Ok(None),
+        }
+    }
+}
+
+// A file containing only the token, as mounted by CI systems. The path is taken
+// from GITHUB_AUTH_TOKEN_FILE for github.com and GITHUB_ENTERPRISE_AUTH_TOKEN_FILE
+// for other hosts.
+pub struct FileProvider {
+    github: Option<PathBuf>,
+    enterprise: Option<PathBuf>,
+}
+
+impl FileProvider {
+    // A file explicitly chosen by the caller is used for every host
+    pub fn new(path: PathBuf) -> Self {
+        FileProvider { github: Some(path.clone()), enterprise: Some(path) }
+    }
+
+    pub fn from_env() -> Self {
+        FileProvider {
+            github: env::var_os("GITHUB_AUTH_TOKEN_FILE").map(PathBuf::from),
+            enterprise: env::var_os("GITHUB_ENTERPRISE_AUTH_TOKEN_FILE").map(PathBuf::from),
+        }
+    }
+}
+
+impl CredentialProvider for FileProvider {
+    fn name(&self) -> &'static str {
+        "token file"
+    }
+
+    fn token(&self, host: &str) -> Result<Option<String>, ReqError> {
+        let path = if is_github_com(host) { &self.github } else { &self.enterprise };
+        let path = match path {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+        let token = fs::read_to_string(path)?;
+        let token = token.trim();
+        if token.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(token.to_string()))
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct GhHost {
+    oauth_token: Option<String>,
+}
+
+// The gh CLI's hosts.yml, in $GH_CONFIG_DIR or ~/.config/gh
+pub struct GhCliProvider {
+    path: Option<PathBuf>,
+}
+
+impl GhCliProvider {
+    pub fn new(path: PathBuf) -> Self {
+        GhCliProvider { path: Some(path) }
+    }
+
+    pub fn from_env() -> Self {
+        let dir = match env::var_os("GH_CONFIG_DIR") {
+            Some(dir) => Some(PathBuf::from(dir)),
+            None => env::var_os("HOME").map(|home| PathBuf::from(home).join(".config").join("gh")),
+        };
+        GhCliProvider { path: dir.map(|dir| dir.join("hosts.yml")) }
+    }
+}
+
+impl CredentialProvider for GhCliProvider {
+    fn name(&self) -> &'static str {
+        "gh CLI config"
+    }
+
+    fn token(&self, host: &str) -> Result<Option<String>, ReqError> {
+        let path = match &self.path {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        };
+        let contents = fs::read_to_string(path)?;
+        // gh versions that keep tokens in the system keyring leave oauth_token out
+        let hosts: HashMap<String, GhHost> = match serde_yaml::from_str(&contents) {
+            Ok(hosts) => hosts,
+            Err(_err) => return Ok(None),
+        };
+        Ok(hosts.get(host).and_then(|h| h.oauth_token.clone()))
+    }
+}
+
+// `git credential fill`, which consults whatever credential helper git is configured with
+pub struct GitCredentialProvider;
+
+impl CredentialProvider for GitCredentialProvider {
+    fn name(&self) -> &'static str {
+        "git credential helper"
+    }
+
+    fn token(&self, host: &str) -> Result<Option<String>, ReqError> {
+        let child = Command::new("git")
+            .args(["credential", "fill"])
+            // never fall back to prompting on the terminal
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GCM_INTERACTIVE", "never")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            // git isn't installed
+            Err(_err) => return Ok(None),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            write!(stdin, "protocol=https\nhost={}\n\n", host)?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        Ok(stdout
+            .lines()
+            .find_map(|line| line.strip_prefix("password="))
+            .filter(|password| !password.is_empty())
+            .map(|password| password.to_string()))
+    }
+}
+
+// Providers are tried in order and the first token found wins
+pub struct CredentialChain {
+    providers: Vec<Box<dyn CredentialProvider + Send + Sync>>,
+}
+
+impl CredentialChain {
+    pub fn new(providers: Vec<Box<dyn CredentialProvider + Send + Sync>>) -> Self {
+        CredentialChain { providers }
+    }
+
+    pub fn token(&self, host: &str) -> Result<Option<String>, ReqError> {
+        for provider in &self.providers {
+            if let Some(token) = provider.token(host)? {
+                eprintln!("using credentials for {} from {}", host, provider.name());
+                return Ok(Some(token));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Default for CredentialChain {
+    fn default() -> Self {
+        CredentialChain::new(vec![
+            Box::new(EnvProvider),
+            Box::new(FileProvider::from_env()),
+            Box::new(GhCliProvider::from_env()),
+            Box::new(GitCredentialProvider),
+        ])
+    }
+}
+
+// The host credentials are stored under for an API base URL. GitHub.com's API
+// lives on api.github.com, while Enterprise serves it from <host>/api/v3.
+pub fn credential_host(api_url: &str) -> String {
+    let host = Url::parse(api_url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_else(|| "github.com".to_string());
+    match host.as_str() {
+        "api.github.com" => "github.com".to_string(),
+        _ => host,
+    }
+}
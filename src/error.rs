@@ -11,11 +11,61 @@ pub enum ReqError {
     #[error("media type not supported for query")]
     UnsupportedMediaType,
     #[error("error communicating with the api")]
-    ConnectionError(#[from] reqwest::Error),
+    ConnectionError(#[source] reqwest::Error),
+    #[error("request to the api timed out")]
+    Timeout(#[source] reqwest::Error),
+    #[error("no data received from the api for {0:?}")]
+    ReadTimeout(std::time::Duration),
     #[error("unknown error communicating with the api")]
     UnknownConnectionError,
     #[error("failed to renew auth token")]
     AuthenticationTimeoutFailure,
      #[error("not found")]
     NotFound,
-}
\ No newline at end of file
+    #[error("conflict with the current state of the repository")]
+    Conflict,
+    #[error("base branch {0} does not exist")]
+    MissingBranch(String),
+    #[error("repository is empty")]
+    EmptyRepository,
+    #[error("fork {0} was not ready in time")]
+    ForkNotReady(String),
+    #[error("{0} has diverged from upstream")]
+    ForkDiverged(String),
+    #[error("no GitHub credentials found in GITHUB_AUTH_TOKEN (GITHUB_ENTERPRISE_AUTH_TOKEN for Enterprise hosts), the matching _FILE variable, gh CLI config, git credential helper or GITHUB_APP_ID")]
+    MissingCredentials,
+    #[error("token is missing a required permission: {0}")]
+    MissingPermission(String),
+    #[error("GitHub App is not installed on {0}")]
+    AppNotInstalled(String),
+    #[error("invalid GitHub App configuration: {0}")]
+    InvalidAppConfig(String),
+    #[error("failed to sign GitHub App token")]
+    JwtError(#[from] jsonwebtoken::errors::Error),
+    #[error("failed to read local file")]
+    IoError(#[from] std::io::Error),
+    #[error("tree {0} has too many entries to list")]
+    TruncatedTree(String),
+    #[error("{0} is not a file")]
+    NotAFile(String),
+    #[error("failed to decode base64 content")]
+    DecodeError(#[from] base64::DecodeError),
+    #[error("invalid manifest: {0}")]
+    InvalidManifest(String),
+    #[error("failed to parse api response")]
+    ParseError(#[from] serde_json::Error),
+    #[error("failed to parse api response")]
+    ResponseDecodeError(#[source] reqwest::Error),
+}
+
+impl From<reqwest::Error> for ReqError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            ReqError::Timeout(err)
+        } else if err.is_decode() {
+            ReqError::ResponseDecodeError(err)
+        } else {
+            ReqError::ConnectionError(err)
+        }
+    }
+}
@@ -0,0 +1,21 @@
+#![allow(dead_code)]
+
+pub mod api_client;
+mod auth;
+pub mod blocking;
+pub mod cache;
+pub mod credentials;
+pub mod error;
+pub mod manifest;
+pub mod pagination;
+pub mod rate_limit;
+pub mod retry;
+pub mod timeouts;
+pub mod update;
+
+// The async client is always built, since the blocking client runs on top of
+// it, but it is only part of the public API with the "async" feature
+#[cfg(feature = "async")]
+pub mod client;
+#[cfg(not(feature = "async"))]
+mod client;
@@ -1,87 +1,376 @@
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use sget_github::api_client::{self, NewPullRequest};
+use sget_github::blocking::GitHubClient;
+use sget_github::error::ReqError;
+use sget_github::manifest::Manifest;
+use sget_github::update::{ChangeKind, FileChange, Update, UpdateOutcome, UpdatePlan, UpdateStatus};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
-#![allow(dead_code)]
+// Exit statuses. Anything not covered below (bad arguments, an invalid
+// manifest, unexpected API responses) exits with 1, as does a batch in which
+// any repository failed.
+//
+// the files are identical to the base branch and nothing was pushed
+const EXIT_NO_CHANGES: u8 = 2;
+// missing or rejected credentials, or a token without the needed permissions
+const EXIT_AUTH: u8 = 3;
+// the branch already exists, a ref update was not a fast-forward, GitHub
+// refused the pull request as a duplicate of one that isn't open, or a fork
+// diverged from upstream
+const EXIT_CONFLICT: u8 = 4;
+// GitHub couldn't be reached, timed out or kept failing after retries
+const EXIT_NETWORK: u8 = 5;
 
-mod api_client;
-mod error;
+// Usage:
+//   sget-github [--fork] [--dry-run] [--output text|json]
+//   sget-github batch <manifest> [--concurrency <n>] [--dry-run] [--output text|json]
+//
+// --dry-run only reads from GitHub and prints what would be committed and the
+// pull request that would be opened. With --output json the result is printed
+// to stdout as a single JSON object; progress and diagnostics always go to stderr.
+#[derive(Debug, Default)]
+struct Options {
+    manifest: Option<PathBuf>,
+    concurrency: Option<usize>,
+    fork: bool,
+    dry_run: bool,
+    json: bool,
+}
+
+fn parse_args(args: &[String]) -> Result<Options> {
+    let mut options = Options::default();
+    let mut args = args.iter().peekable();
+    let batch = args.peek().map(|arg| arg.as_str()) == Some("batch");
+    if batch {
+        args.next();
+    }
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fork" => options.fork = true,
+            "--dry-run" => options.dry_run = true,
+            "--output" => match args.next().map(String::as_str) {
+                Some("json") => options.json = true,
+                Some("text") => options.json = false,
+                _ => bail!("--output must be text or json"),
+            },
+            "--concurrency" if batch => {
+                let value = args.next().context("--concurrency needs a value")?;
+                options.concurrency = Some(value.parse().context("invalid --concurrency")?);
+            }
+            _ if batch && options.manifest.is_none() && !arg.starts_with("--") => {
+                options.manifest = Some(PathBuf::from(arg));
+            }
+            _ => bail!("unexpected argument {}", arg),
+        }
+    }
+    if batch && options.manifest.is_none() {
+        bail!("usage: sget-github batch <manifest> [--concurrency <n>] [--dry-run] [--output text|json]");
+    }
+    Ok(options)
+}
+
+// The result of updating one repository, as printed by --output json
+#[derive(Serialize, Debug, Default)]
+struct Report {
+    repository: String,
+    // "created", "no_changes", "bootstrapped" or "failed"
+    status: &'static str,
+    dry_run: bool,
+    branch: Option<String>,
+    commit_sha: Option<String>,
+    tree_sha: Option<String>,
+    pr_number: Option<u64>,
+    pr_url: Option<String>,
+    error: Option<String>,
+    // only with --dry-run
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plan: Option<PlanReport>,
+    #[serde(skip)]
+    exit_code: u8,
+}
+
+// What --dry-run found would be committed and opened
+#[derive(Serialize, Debug)]
+struct PlanReport {
+    base_sha: Option<String>,
+    commit_message: String,
+    changes: Vec<FileChange>,
+    pull_request: Option<NewPullRequest>,
+}
+
+impl Report {
+    fn outcome(update: &Update, outcome: UpdateOutcome) -> Self {
+        Report {
+            repository: repository(update),
+            status: outcome.status.as_str(),
+            exit_code: status_exit_code(outcome.status),
+            branch: outcome.branch,
+            commit_sha: outcome.commit_sha,
+            tree_sha: outcome.tree_sha,
+            pr_number: outcome.pr_number,
+            pr_url: outcome.pr_url,
+            ..Default::default()
+        }
+    }
+
+    fn plan(update: &Update, plan: &UpdatePlan) -> Self {
+        Report {
+            repository: repository(update),
+            status: plan.status.as_str(),
+            exit_code: status_exit_code(plan.status),
+            dry_run: true,
+            branch: Some(plan.branch.clone()).filter(|branch| !branch.is_empty()),
+            plan: Some(PlanReport {
+                base_sha: plan.base_sha.clone(),
+                commit_message: plan.commit_message.clone(),
+                changes: plan.changes.clone(),
+                pull_request: plan.pull_request.clone(),
+            }),
+            ..Default::default()
+        }
+    }
 
-use anyhow::Result;
-use random_string::generate;
+    fn failure(repository: String, dry_run: bool, err: ReqError) -> Self {
+        Report {
+            repository,
+            status: "failed",
+            exit_code: error_exit_code(&err),
+            dry_run,
+            error: Some(format!("{:#}", anyhow::Error::new(err))),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct BatchReport {
+    results: Vec<Report>,
+}
+
+fn repository(update: &Update) -> String {
+    format!("{}/{}", update.owner, update.repo)
+}
+
+fn status_exit_code(status: UpdateStatus) -> u8 {
+    match status {
+        UpdateStatus::NoChanges => EXIT_NO_CHANGES,
+        UpdateStatus::Created | UpdateStatus::Bootstrapped => 0,
+    }
+}
+
+fn error_exit_code(err: &ReqError) -> u8 {
+    match err {
+        ReqError::AuthError
+        | ReqError::MissingCredentials
+        | ReqError::MissingPermission(_)
+        | ReqError::AuthenticationTimeoutFailure
+        | ReqError::AppNotInstalled(_)
+        | ReqError::InvalidAppConfig(_)
+        | ReqError::JwtError(_) => EXIT_AUTH,
+        ReqError::Conflict | ReqError::ForkDiverged(_) => EXIT_CONFLICT,
+        ReqError::ConnectionError(_)
+        | ReqError::Timeout(_)
+        | ReqError::ReadTimeout(_)
+        | ReqError::UnknownConnectionError
+        | ReqError::TooManyRequest => EXIT_NETWORK,
+        _ => 1,
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error: {:#}", err);
+            let code = err.downcast_ref::<ReqError>().map(error_exit_code).unwrap_or(1);
+            ExitCode::from(code)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode> {
+    let options = parse_args(args)?;
+    let client = GitHubClient::new();
+
+    if let Some(manifest) = &options.manifest {
+        return batch(&client, manifest, &options);
+    }
 
-fn main() -> Result<()> {
     let owner = "jyotsna-penumaka";
     let repo = "new";
     let base = "main";
-    let maintainer_can_modify = true;
-    let draft = false;
-    
-    // get the SHA of the head
-    let head_sha = api_client::get_base(
-        owner,
-        repo,
-        base
-    )?;
-    println!("head_sha: {:?}", head_sha);
-
-    // Generate random branch name
-    let charset = "1234567890";
-    let branch_name = generate(6, charset);
-    let generated_ref = format!("refs/heads/{}", branch_name);
-
-    let get_ref = api_client::get_ref(owner,
-        repo,
-        generated_ref,
-        head_sha.clone()
-    )?;
-    println!("get_ref: {:?}", get_ref);
-
-    let tree_sha = api_client::create_tree(
-        owner,
-        repo,
-        head_sha.clone()
-    )?;
-    println!("tree_sha: {:?}", tree_sha);
-
-    let parent_commit = api_client::get_parent_commit(
-        owner,
-        repo,
-        head_sha.clone()
-    )?;
-
-    let mut parents = parent_commit.parents;
-
-    if parents.capacity() == 0 {
-        println!("Parent's capacity is 0");
-        let root = api_client::Parent {
-            sha: parent_commit.sha,
-            url: parent_commit.url,
-            html_url: parent_commit.html_url
+    let files = vec![api_client::TreeFile::new("README.md", "Hello World!")];
+
+    let mut update = Update::new(owner, repo, base, files);
+    // With --fork the branch and commit go to a fork of the repository, for
+    // credentials that can't push upstream, and the pull request is opened
+    // from the fork
+    update.fork = options.fork;
+
+    let report = if options.dry_run {
+        match client.plan_update(&update) {
+            Ok(plan) => {
+                if !options.json {
+                    print_plan(&update, &plan);
+                }
+                Report::plan(&update, &plan)
+            }
+            Err(err) => Report::failure(repository(&update), true, err),
+        }
+    } else {
+        match client.apply_update(&update) {
+            Ok(outcome) => Report::outcome(&update, outcome),
+            Err(err) => Report::failure(repository(&update), false, err),
+        }
+    };
+
+    if options.json {
+        println!("{}", serde_json::to_string(&report)?);
+    } else if let Some(error) = &report.error {
+        eprintln!("error: {}", error);
+    } else if !options.dry_run {
+        print_outcome(&report);
+    }
+    Ok(ExitCode::from(report.exit_code))
+}
+
+// Open the same kind of pull request across every repository in a manifest.
+// A failure in one repository doesn't stop the others; the exit status is
+// non-zero if any of them failed.
+fn batch(client: &GitHubClient, manifest_path: &Path, options: &Options) -> Result<ExitCode> {
+    let manifest = Manifest::load(manifest_path)
+        .with_context(|| format!("failed to load {}", manifest_path.display()))?;
+    let dir = manifest_path.parent().unwrap_or_else(|| Path::new("."));
+    // A repository whose files can't be read fails on its own; the others still run
+    let entries: Vec<Result<Update, ReqError>> = manifest
+        .repositories
+        .iter()
+        .map(|entry| entry.update(dir))
+        .collect();
+    let updates: Vec<Update> = entries.iter().filter_map(|entry| entry.as_ref().ok().cloned()).collect();
+
+    let runs: Vec<Report> = if options.dry_run {
+        updates
+            .iter()
+            .map(|update| match client.plan_update(update) {
+                Ok(plan) => {
+                    if !options.json {
+                        print_plan(update, &plan);
+                    }
+                    Report::plan(update, &plan)
+                }
+                Err(err) => Report::failure(repository(update), true, err),
+            })
+            .collect()
+    } else {
+        let concurrency = options.concurrency.unwrap_or(manifest.concurrency);
+        updates
+            .iter()
+            .zip(client.apply_updates(&updates, concurrency))
+            .map(|(update, result)| match result {
+                Ok(outcome) => Report::outcome(update, outcome),
+                Err(err) => Report::failure(repository(update), false, err),
+            })
+            .collect()
+    };
+    let mut runs = runs.into_iter();
+    let reports: Vec<Report> = manifest
+        .repositories
+        .iter()
+        .zip(entries)
+        .map(|(entry, update)| match update {
+            Ok(_) => runs.next().expect("one report per runnable repository"),
+            Err(err) => Report::failure(format!("{}/{}", entry.owner, entry.repo), options.dry_run, err),
+        })
+        .collect();
+
+    let failed = reports.iter().any(|report| report.error.is_some());
+    if options.json {
+        println!("{}", serde_json::to_string(&BatchReport { results: reports })?);
+    } else {
+        print_summary(&reports);
+    }
+    if failed {
+        return Ok(ExitCode::FAILURE);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+fn print_outcome(report: &Report) {
+    match report.status {
+        "created" => println!("pull request: {}", report.pr_url.as_deref().unwrap_or_default()),
+        "bootstrapped" => println!("initial commit: {}", report.commit_sha.as_deref().unwrap_or_default()),
+        _ => println!("no changes"),
+    }
+}
+
+fn print_summary(reports: &[Report]) {
+    let rows: Vec<[String; 3]> = reports
+        .iter()
+        .map(|report| {
+            let detail = match report.status {
+                "created" => report.pr_url.clone(),
+                "bootstrapped" => report.commit_sha.clone(),
+                "failed" => report.error.clone(),
+                _ => report.branch.clone().filter(|_| report.dry_run),
+            };
+            [report.repository.clone(), report.status.to_string(), detail.unwrap_or_default()]
+        })
+        .collect();
+
+    let header = ["REPOSITORY".to_string(), "STATUS".to_string(), "RESULT".to_string()];
+    let width = |column: usize| {
+        rows.iter()
+            .chain([&header])
+            .map(|row| row[column].len())
+            .max()
+            .unwrap_or(0)
+    };
+    let (repository_width, status_width) = (width(0), width(1));
+    println!();
+    for row in [&header].into_iter().chain(&rows) {
+        let line = format!("{:<rw$}  {:<sw$}  {}", row[0], row[1], row[2], rw = repository_width, sw = status_width);
+        println!("{}", line.trim_end());
+    }
+    let failed = reports.iter().filter(|report| report.error.is_some()).count();
+    println!("\n{} repositories, {} failed", reports.len(), failed);
+}
+
+fn print_plan(update: &Update, plan: &UpdatePlan) {
+    println!("{}/{} (base {})", update.owner, update.repo, update.base);
+    match plan.status {
+        UpdateStatus::NoChanges => println!("  no changes, {} is already up to date", update.base),
+        UpdateStatus::Bootstrapped => println!("  repository is empty, {} would be created with an initial commit", plan.branch),
+        UpdateStatus::Created => {
+            if update.fork {
+                println!("  would fork the repository and sync its {} branch first", update.base);
+            }
+            println!("  branch: {}", plan.branch);
+        }
+    }
+    if let Some(base_sha) = &plan.base_sha {
+        println!("  base commit: {}", base_sha);
+    }
+    println!("  commit message: {}", plan.commit_message);
+    println!("  files:");
+    for change in &plan.changes {
+        let kind = match change.kind {
+            ChangeKind::Added => "A",
+            ChangeKind::Modified => "M",
+            ChangeKind::Unchanged => "=",
         };
-        parents.push(root);
-    }
-
-    // Make a commit
-    let commit_sha = api_client::push_commit(
-        get_ref,
-        owner,
-        repo,
-        tree_sha.clone(),
-        parents
-    )?;
-    println!("commit_sha: {:?}", commit_sha);
-
-    // Create a pull request
-    let pr_title = format!("This pull request created by {} merges {} into {}", owner, branch_name, base);
-
-    let pr_url = api_client::create_pr(
-        owner, 
-        repo,
-        &pr_title, 
-        branch_name.to_string(), 
-        base.to_string(), 
-        maintainer_can_modify,
-        draft
-    )?;  
-    println!("pr_url: {:?}", pr_url);
-    
-    Ok(())
+        println!("    {} {} ({})", kind, change.path, change.sha);
+    }
+    if let Some(pull_request) = &plan.pull_request {
+        let payload = serde_json::to_string_pretty(pull_request).unwrap_or_default();
+        println!("  pull request (POST /repos/{}/{}/pulls):", update.owner, update.repo);
+        for line in payload.lines() {
+            println!("    {}", line);
+        }
+    }
+    println!();
 }
@@ -0,0 +1,192 @@
+// Batch manifests, listing the repositories to update and the files to commit
+// to each. Manifests are YAML, which also accepts JSON:
+//
+//   concurrency: 4
+//   repositories:
+//     - owner: octo-org
+//       repo: service
+//       base: main
+//       files:
+//         - path: .sigstore/signature.pem
+//           source: signatures/service.pem
+//         - path: README.md
+//           content: "Hello World!"
+use crate::api_client::TreeFile;
+use crate::error::ReqError;
+use crate::update::Update;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+#[derive(Deserialize, Debug)]
+pub struct Manifest {
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    pub repositories: Vec<ManifestRepository>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ManifestRepository {
+    pub owner: String,
+    pub repo: String,
+    #[serde(default = "default_base")]
+    pub base: String,
+    #[serde(default)]
+    pub fork: bool,
+    #[serde(default)]
+    pub draft: bool,
+    pub files: Vec<ManifestFile>,
+}
+
+// A file's contents are given inline or read from a local file, relative to
+// the manifest
+#[derive(Deserialize, Debug)]
+pub struct ManifestFile {
+    pub path: String,
+    pub content: Option<String>,
+    pub source: Option<PathBuf>,
+    pub mode: Option<String>,
+}
+
+fn default_concurrency() -> usize {
+    DEFAULT_CONCURRENCY
+}
+
+fn default_base() -> String {
+    "main".to_string()
+}
+
+impl Manifest {
+    pub fn parse(contents: &str) -> Result<Self, ReqError> {
+        serde_yaml::from_str(contents).map_err(|err| ReqError::InvalidManifest(err.to_string()))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ReqError> {
+        Manifest::parse(&fs::read_to_string(path)?)
+    }
+
+    // Turn every entry into an Update, reading file sources relative to dir
+    pub fn updates(&self, dir: &Path) -> Result<Vec<Update>, ReqError> {
+        self.repositories.iter().map(|entry| entry.update(dir)).collect()
+    }
+}
+
+impl ManifestRepository {
+    pub fn update(&self, dir: &Path) -> Result<Update, ReqError> {
+        let files = self
+            .files
+            .iter()
+            .map(|file| file.tree_file(dir))
+            .collect::<Result<Vec<_>, ReqError>>()?;
+        let mut update = Update::new(&self.owner, &self.repo, &self.base, files);
+        update.fork = self.fork;
+        update.draft = self.draft;
+        Ok(update)
+    }
+}
+
+impl ManifestFile {
+    fn tree_file(&self, dir: &Path) -> Result<TreeFile, ReqError> {
+        let content = match (&self.content, &self.source) {
+            (Some(content), None) => content.clone().into_bytes(),
+            (None, Some(source)) => fs::read(dir.join(source))?,
+            _ => {
+                return Err(ReqError::InvalidManifest(format!(
+                    "{} needs exactly one of `content` or `source`",
+                    self.path
+                )))
+            }
+        };
+        let mut file = TreeFile::new(&self.path, content);
+        if let Some(mode) = &self.mode {
+            file.mode = mode.clone();
+        }
+        Ok(file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yaml_manifest() {
+        let manifest = Manifest::parse(
+            "concurrency: 2
+repositories:
+  - owner: octo-org
+    repo: service
+    base: develop
+    fork: true
+    draft: true
+    files:
+      - path: bin/run
+        content: \"#!/bin/sh\"
+        mode: \"100755\"
+",
+        )
+        .unwrap();
+        assert_eq!(manifest.concurrency, 2);
+
+        let updates = manifest.updates(Path::new(".")).unwrap();
+        let update = &updates[0];
+        assert_eq!((update.owner.as_str(), update.repo.as_str(), update.base.as_str()), ("octo-org", "service", "develop"));
+        assert!(update.fork && update.draft);
+        assert_eq!(update.files[0].path, "bin/run");
+        assert_eq!(update.files[0].content, b"#!/bin/sh");
+        assert_eq!(update.files[0].mode, "100755");
+    }
+
+    #[test]
+    fn json_manifest_with_defaults() {
+        let manifest = Manifest::parse(
+            r#"{"repositories": [{"owner": "octo-org", "repo": "service", "files": [{"path": "README.md", "content": "Hello World!"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.concurrency, DEFAULT_CONCURRENCY);
+
+        let update = manifest.repositories[0].update(Path::new(".")).unwrap();
+        assert_eq!(update.base, "main");
+        assert!(!update.fork && !update.draft);
+        assert_eq!(update.files[0].mode, "100644");
+    }
+
+    #[test]
+    fn source_is_read_relative_to_the_manifest() {
+        let dir = std::env::temp_dir().join(format!("sget-github-manifest-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("signature.pem"), "-----BEGIN-----").unwrap();
+
+        let manifest = Manifest::parse(
+            "repositories:
+  - owner: octo-org
+    repo: service
+    files:
+      - path: .sigstore/signature.pem
+        source: signature.pem
+",
+        )
+        .unwrap();
+        let updates = manifest.updates(&dir);
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(updates.unwrap()[0].files[0].content, b"-----BEGIN-----");
+    }
+
+    #[test]
+    fn content_and_source_are_exclusive() {
+        for file in ["{path: a, content: x, source: b}", "{path: a}"] {
+            let manifest = Manifest::parse(&format!("repositories: [{{owner: o, repo: r, files: [{}]}}]", file)).unwrap();
+            match manifest.updates(Path::new(".")) {
+                Err(ReqError::InvalidManifest(message)) => assert!(message.contains("exactly one of"), "{}", message),
+                other => panic!("expected InvalidManifest for {}, got {:?}", file, other),
+            }
+        }
+    }
+
+    #[test]
+    fn missing_fields_are_invalid() {
+        assert!(matches!(Manifest::parse("repositories: [{owner: o}]"), Err(ReqError::InvalidManifest(_))));
+    }
+}
@@ -0,0 +1,98 @@
+use serde::Deserialize;
+
+// GitHub returns at most 100 items per page
+pub const MAX_PER_PAGE: u32 = 100;
+
+// How a list endpoint is paged through
+#[derive(Debug, Clone, Copy)]
+pub struct PageOptions {
+    pub per_page: u32,
+    // stop after this many items, without fetching further pages
+    pub max_items: Option<usize>,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        PageOptions {
+            per_page: MAX_PER_PAGE,
+            max_items: None,
+        }
+    }
+}
+
+impl PageOptions {
+    pub fn max_items(max_items: usize) -> Self {
+        PageOptions {
+            max_items: Some(max_items),
+            ..PageOptions::default()
+        }
+    }
+}
+
+// Most list endpoints return a bare array, but some (check runs, installation
+// repositories) wrap the items in an object next to a total_count
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum Page<T> {
+    Items(Vec<T>),
+    Wrapped(WrappedPage<T>),
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct WrappedPage<T> {
+    #[serde(alias = "check_runs", alias = "repositories")]
+    items: Vec<T>,
+}
+
+impl<T> Page<T> {
+    pub(crate) fn into_items(self) -> Vec<T> {
+        match self {
+            Page::Items(items) => items,
+            Page::Wrapped(page) => page.items,
+        }
+    }
+}
+
+// Find the rel="next" URL in a Link header such as
+// <https://api.github.com/...&page=2>; rel="next", <https://api.github.com/...&page=5>; rel="last"
+pub fn next_link(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let mut pieces = part.split(';');
+        let url = pieces.next()?.trim();
+        let is_next = pieces.any(|param| param.trim() == r#"rel="next""#);
+        if !is_next {
+            return None;
+        }
+        Some(url.trim_start_matches('<').trim_end_matches('>').to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_link_among_other_rels() {
+        let link = r#"<https://api.github.com/repositories/1/issues?page=1>; rel="prev", <https://api.github.com/repositories/1/issues?page=3>; rel="next", <https://api.github.com/repositories/1/issues?page=5>; rel="last", <https://api.github.com/repositories/1/issues?page=1>; rel="first""#;
+        assert_eq!(
+            next_link(link).as_deref(),
+            Some("https://api.github.com/repositories/1/issues?page=3")
+        );
+    }
+
+    #[test]
+    fn no_next_link_on_the_last_page() {
+        let link = r#"<https://api.github.com/repositories/1/issues?page=4>; rel="prev", <https://api.github.com/repositories/1/issues?page=1>; rel="first""#;
+        assert_eq!(next_link(link), None);
+        assert_eq!(next_link(""), None);
+    }
+
+    #[test]
+    fn wrapped_and_bare_pages() {
+        let wrapped: Page<u32> = serde_json::from_str(r#"{"total_count": 2, "check_runs": [1, 2]}"#).unwrap();
+        assert_eq!(wrapped.into_items(), vec![1, 2]);
+
+        let bare: Page<u32> = serde_json::from_str("[3, 4]").unwrap();
+        assert_eq!(bare.into_items(), vec![3, 4]);
+    }
+}
@@ -0,0 +1,184 @@
+use chrono::{DateTime, TimeZone, Utc};
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::time::Duration;
+
+// Secondary (abuse) limits don't say how long to wait; GitHub asks for at least a minute
+const SECONDARY_BACKOFF_SECS: u64 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct RateLimit {
+    pub limit: u64,
+    pub remaining: u64,
+    #[serde(default)]
+    pub used: u64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub reset: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+pub(crate) struct RateLimitOverview {
+    pub rate: RateLimit,
+}
+
+impl RateLimit {
+    // Read the X-RateLimit-* headers GitHub sends with every API response
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let header = |name: &str| -> Option<u64> { headers.get(name)?.to_str().ok()?.parse().ok() };
+        let reset = header("x-ratelimit-reset")?;
+        Some(RateLimit {
+            limit: header("x-ratelimit-limit")?,
+            remaining: header("x-ratelimit-remaining")?,
+            used: header("x-ratelimit-used").unwrap_or(0),
+            reset: Utc.timestamp_opt(reset as i64, 0).single()?,
+        })
+    }
+
+    // Time left until the budget is replenished, if it is currently exhausted
+    pub fn wait_time(&self) -> Option<Duration> {
+        if self.remaining > 0 {
+            return None;
+        }
+        // add a second of slack so we don't wake up just before the reset
+        let secs = (self.reset - Utc::now()).num_seconds() + 1;
+        if secs <= 0 {
+            return None;
+        }
+        Some(Duration::from_secs(secs as u64))
+    }
+}
+
+// Decide whether a response was rejected by a primary or secondary rate limit
+// and, if so, how long to wait before retrying it. `attempt` counts previous
+// retries and is used to back off exponentially on secondary limits.
+pub fn throttled(status: StatusCode, headers: &HeaderMap, attempt: u32) -> Option<Duration> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+
+    let retry_after = headers
+        .get("retry-after")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(secs) = retry_after {
+        return Some(Duration::from_secs(secs));
+    }
+
+    // primary limit: sleep until the budget resets
+    if let Some(wait) = RateLimit::from_headers(headers).and_then(|limit| limit.wait_time()) {
+        return Some(wait);
+    }
+
+    // a 403 with budget left is either a secondary limit, which only the body
+    // tells apart (see is_secondary_limit), or an ordinary permission error
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Some(secondary_backoff(attempt));
+    }
+    None
+}
+
+pub fn secondary_backoff(attempt: u32) -> Duration {
+    Duration::from_secs(SECONDARY_BACKOFF_SECS << attempt.min(4))
+}
+
+#[derive(Deserialize, Debug)]
+struct ErrorBody {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    documentation_url: String,
+}
+
+// Whether the body of a 403 is GitHub's secondary (abuse) rate limit error,
+// which usually comes without Retry-After and with primary budget left
+pub fn is_secondary_limit(body: &[u8]) -> bool {
+    let body: ErrorBody = match serde_json::from_slice(body) {
+        Ok(body) => body,
+        Err(_err) => return false,
+    };
+    let message = body.message.to_lowercase();
+    message.contains("secondary rate limit")
+        || message.contains("abuse detection")
+        || body.documentation_url.contains("secondary-rate-limits")
+        || body.documentation_url.contains("abuse-rate-limits")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, String)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn budget(remaining: u64, reset_in: i64) -> HeaderMap {
+        let reset = Utc::now().timestamp() + reset_in;
+        headers(&[
+            ("x-ratelimit-limit", "5000".to_string()),
+            ("x-ratelimit-remaining", remaining.to_string()),
+            ("x-ratelimit-used", (5000 - remaining).to_string()),
+            ("x-ratelimit-reset", reset.to_string()),
+        ])
+    }
+
+    #[test]
+    fn rate_limit_from_headers() {
+        let limit = RateLimit::from_headers(&budget(42, 600)).unwrap();
+        assert_eq!((limit.limit, limit.remaining, limit.used), (5000, 42, 4958));
+        assert_eq!(limit.wait_time(), None);
+        assert_eq!(RateLimit::from_headers(&HeaderMap::new()), None);
+
+        let exhausted = RateLimit::from_headers(&budget(0, 600)).unwrap();
+        let wait = exhausted.wait_time().unwrap();
+        assert!(wait > Duration::from_secs(590) && wait <= Duration::from_secs(601), "{:?}", wait);
+        // a reset in the past needs no wait
+        assert_eq!(RateLimit::from_headers(&budget(0, -10)).unwrap().wait_time(), None);
+    }
+
+    #[test]
+    fn retry_after_is_honoured() {
+        let headers = headers(&[("retry-after", "30".to_string())]);
+        assert_eq!(throttled(StatusCode::FORBIDDEN, &headers, 0), Some(Duration::from_secs(30)));
+        assert_eq!(throttled(StatusCode::TOO_MANY_REQUESTS, &headers, 2), Some(Duration::from_secs(30)));
+        assert_eq!(throttled(StatusCode::OK, &headers, 0), None);
+    }
+
+    #[test]
+    fn exhausted_budget_waits_for_reset() {
+        let wait = throttled(StatusCode::FORBIDDEN, &budget(0, 120), 0).unwrap();
+        assert!(wait > Duration::from_secs(110) && wait <= Duration::from_secs(121), "{:?}", wait);
+    }
+
+    #[test]
+    fn too_many_requests_without_headers_backs_off() {
+        let empty = HeaderMap::new();
+        assert_eq!(throttled(StatusCode::TOO_MANY_REQUESTS, &empty, 0), Some(Duration::from_secs(60)));
+        assert_eq!(throttled(StatusCode::TOO_MANY_REQUESTS, &empty, 1), Some(Duration::from_secs(120)));
+    }
+
+    #[test]
+    fn forbidden_with_budget_left_is_not_throttled() {
+        // told apart from a secondary limit by its body, in send_with
+        assert_eq!(throttled(StatusCode::FORBIDDEN, &budget(100, 600), 0), None);
+        assert_eq!(throttled(StatusCode::FORBIDDEN, &HeaderMap::new(), 0), None);
+    }
+
+    #[test]
+    fn secondary_limit_bodies() {
+        assert!(is_secondary_limit(
+            br#"{"message":"You have exceeded a secondary rate limit. Please wait a few minutes before you try again.","documentation_url":"https://docs.github.com/rest/overview/resources-in-the-rest-api#secondary-rate-limits"}"#
+        ));
+        assert!(is_secondary_limit(
+            br#"{"message":"You have triggered an abuse detection mechanism.","documentation_url":"https://developer.github.com/v3/#abuse-rate-limits"}"#
+        ));
+        assert!(!is_secondary_limit(
+            br#"{"message":"Resource not accessible by integration","documentation_url":"https://docs.github.com/rest"}"#
+        ));
+        assert!(!is_secondary_limit(b"<html>forbidden</html>"));
+    }
+}
@@ -0,0 +1,78 @@
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use std::time::Duration;
+
+// How requests that fail for transient reasons (network errors, 5xx from GitHub)
+// are retried
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // total number of attempts, including the first one
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // randomise each delay so that parallel jobs don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    // Never retry
+    pub fn none() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    // Delay before retry number `attempt` (starting at 0): exponential backoff,
+    // capped at max_backoff, with "full jitter" if enabled
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .initial_backoff
+            .saturating_mul(1 << attempt.min(16))
+            .min(self.max_backoff);
+        if !self.jitter {
+            return delay;
+        }
+        let millis = delay.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=millis))
+    }
+}
+
+// Methods that can be replayed without side effects if the first attempt
+// did (or might have) reached GitHub
+pub fn is_idempotent(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::PUT | Method::PATCH | Method::DELETE | Method::OPTIONS)
+}
+
+// Server-side failures that are usually gone on the next attempt
+pub fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::INTERNAL_SERVER_ERROR
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+// Whether a failed request may be retried. Requests that never reached GitHub
+// (connection refused, DNS failure) are always safe to send again; requests that
+// timed out or were cut off mid-flight may already have taken effect, so they
+// are only retried when replaying them is harmless.
+pub fn is_retryable_error(err: &reqwest::Error, replayable: bool) -> bool {
+    if err.is_connect() {
+        return true;
+    }
+    replayable && (err.is_timeout() || err.is_request())
+}
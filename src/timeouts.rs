@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+// Operations that upload file contents and so get the longer upload timeout
+const UPLOAD_OPERATIONS: &[&str] = &["create_tree", "create_blob"];
+
+// Timeouts used by the client. `request` and `upload` bound a whole request,
+// from sending it to reading the last byte of the response; `operations`
+// overrides them for individual client methods, keyed by method name. `read`
+// bounds the wait for each part of a response body once the headers arrived,
+// so a stalled download fails long before a generous total timeout.
+#[derive(Debug, Clone)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub read: Duration,
+    pub request: Duration,
+    pub upload: Duration,
+    pub operations: HashMap<String, Duration>,
+}
+
+impl Default for Timeouts {
+    fn default() -> Self {
+        Timeouts {
+            connect: Duration::from_secs(10),
+            read: Duration::from_secs(30),
+            request: Duration::from_secs(30),
+            upload: Duration::from_secs(300),
+            operations: HashMap::new(),
+        }
+    }
+}
+
+impl Timeouts {
+    // Total timeout for a client method
+    pub fn for_operation(&self, operation: &str) -> Duration {
+        if let Some(timeout) = self.operations.get(operation) {
+            return *timeout;
+        }
+        if UPLOAD_OPERATIONS.contains(&operation) {
+            return self.upload;
+        }
+        self.request
+    }
+}
@@ -0,0 +1,333 @@
+// The branch -> tree -> commit -> pull request flow, for one repository or a
+// batch of them
+use crate::api_client::{blob_sha, NewPullRequest, TreeFile};
+use crate::client::{GitHubClient, COMMIT_MESSAGE, INITIAL_COMMIT_MESSAGE};
+use crate::error::ReqError;
+use futures::stream::{self, StreamExt};
+use random_string::generate;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// Files to propose to a repository
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub owner: String,
+    pub repo: String,
+    pub base: String,
+    pub files: Vec<TreeFile>,
+    // push the branch to a fork and open the pull request from there
+    pub fork: bool,
+    pub maintainer_can_modify: bool,
+    pub draft: bool,
+}
+
+impl Update {
+    pub fn new(owner: impl Into<String>, repo: impl Into<String>, base: impl Into<String>, files: Vec<TreeFile>) -> Self {
+        Update {
+            owner: owner.into(),
+            repo: repo.into(),
+            base: base.into(),
+            files,
+            fork: false,
+            maintainer_can_modify: true,
+            draft: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateStatus {
+    // a pull request was opened (or an identical one was already open)
+    Created,
+    // the files already match the base branch, nothing was pushed
+    NoChanges,
+    // the repository was empty, so the files were committed straight to the base branch
+    Bootstrapped,
+}
+
+impl UpdateStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UpdateStatus::Created => "created",
+            UpdateStatus::NoChanges => "no_changes",
+            UpdateStatus::Bootstrapped => "bootstrapped",
+        }
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Unchanged,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct FileChange {
+    pub path: String,
+    pub kind: ChangeKind,
+    // SHA of the blob the file would be committed as
+    pub sha: String,
+}
+
+// What apply_update would do, worked out using only read requests
+#[derive(Debug, Clone)]
+pub struct UpdatePlan {
+    pub status: UpdateStatus,
+    pub base_sha: Option<String>,
+    pub branch: String,
+    pub commit_message: String,
+    pub changes: Vec<FileChange>,
+    pub pull_request: Option<NewPullRequest>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateOutcome {
+    pub status: UpdateStatus,
+    pub branch: Option<String>,
+    pub tree_sha: Option<String>,
+    pub commit_sha: Option<String>,
+    pub pr_number: Option<u64>,
+    pub pr_url: Option<String>,
+}
+
+impl GitHubClient {
+    pub async fn apply_update(&self, update: &Update) -> Result<UpdateOutcome, ReqError> {
+        let (owner, repo, base) = (update.owner.as_str(), update.repo.as_str(), update.base.as_str());
+
+        // In fork mode the branch and commit go to a fork of the repository, for
+        // credentials that can't push upstream. Creating the branch, commit and
+        // pull request all need credentials with write access to the repository
+        // they are pushed to, so check for them before mutating anything.
+        let (head_owner, head_repo) = if update.fork {
+            self.require_credentials(owner, repo).await?;
+            // wait_for_fork takes a missing base branch for a fork that is still
+            // being copied, so make sure it exists upstream first
+            self.get_base(owner, repo, base).await?;
+            let fork = self.create_fork(owner, repo, None).await?;
+            let fork_owner = fork.owner.map(|user| user.login).ok_or(ReqError::BadRequest)?;
+            self.wait_for_fork(&fork_owner, &fork.name, base).await?;
+            eprintln!("using fork {}/{}", fork_owner, fork.name);
+            self.preflight(&fork_owner, &fork.name).await?;
+            // Branch from upstream's current base rather than the fork's copy, which
+            // may be stale
+            self.sync_fork(&fork_owner, &fork.name, owner, repo, base).await?;
+            (fork_owner, fork.name)
+        } else {
+            self.preflight(owner, repo).await?;
+            (owner.to_string(), repo.to_string())
+        };
+        let (head_owner, head_repo) = (head_owner.as_str(), head_repo.as_str());
+
+        // get the SHA of the head
+        let head_sha = match self.get_base(head_owner, head_repo, base).await {
+            Ok(head_sha) => head_sha,
+            // Nothing to open a pull request against yet, so commit the files
+            // straight to the base branch
+            Err(ReqError::EmptyRepository) => {
+                eprintln!("{}/{} is empty, creating {} with an initial commit", head_owner, head_repo, base);
+                let commit_sha = self.bootstrap_repository(head_owner, head_repo, base, &update.files).await?;
+                return Ok(UpdateOutcome {
+                    status: UpdateStatus::Bootstrapped,
+                    branch: Some(base.to_string()),
+                    tree_sha: None,
+                    commit_sha: Some(commit_sha),
+                    pr_number: None,
+                    pr_url: None,
+                });
+            }
+            Err(err) => return Err(err),
+        };
+        eprintln!("head_sha: {:?}", head_sha);
+
+        let tree_sha = self.create_tree(head_owner, head_repo, head_sha.clone(), &update.files).await?;
+        eprintln!("tree_sha: {:?}", tree_sha);
+
+        let parent_commit = self.get_parent_commit(head_owner, head_repo, head_sha.clone()).await?;
+
+        // The files already match the base branch, so a commit would be empty and
+        // GitHub would refuse the pull request. Stop before creating the branch.
+        if parent_commit.tree.sha == tree_sha {
+            eprintln!("no changes to commit, {} is already up to date", base);
+            return Ok(UpdateOutcome {
+                status: UpdateStatus::NoChanges,
+                branch: None,
+                tree_sha: Some(tree_sha),
+                commit_sha: None,
+                pr_number: None,
+                pr_url: None,
+            });
+        }
+
+        let branch_name = random_branch_name();
+        let generated_ref = format!("refs/heads/{}", branch_name);
+
+        let get_ref = self.get_ref(head_owner, head_repo, generated_ref, head_sha.clone()).await?;
+        eprintln!("get_ref: {:?}", get_ref);
+
+        // Make a commit on top of the base branch head
+        let commit_sha = self.push_commit(get_ref, head_owner, head_repo, tree_sha.clone(), head_sha.clone()).await?;
+        eprintln!("commit_sha: {:?}", commit_sha);
+
+        // Create a pull request
+        let pr_title = pr_title(owner, &branch_name, base);
+
+        // A branch in a fork is named as "<fork owner>:<branch>"
+        let head = if update.fork {
+            format!("{}:{}", head_owner, branch_name)
+        } else {
+            branch_name.clone()
+        };
+
+        let pr = self.create_pr(
+            owner,
+            repo,
+            &pr_title,
+            head,
+            base.to_string(),
+            update.maintainer_can_modify,
+            update.draft,
+        ).await?;
+        let pr_url = pr.html_url.map(|url| url.to_string()).unwrap_or(pr.url);
+        eprintln!("pr_url: {:?}", pr_url);
+
+        Ok(UpdateOutcome {
+            status: UpdateStatus::Created,
+            branch: Some(branch_name),
+            tree_sha: Some(tree_sha),
+            commit_sha: Some(commit_sha),
+            pr_number: Some(pr.number),
+            pr_url: Some(pr_url),
+        })
+    }
+
+    // Plan an update without changing anything: reads the base branch and its
+    // head commit and tree, and compares the files to what is already there.
+    // In fork mode the plan is made against upstream, since the fork may not
+    // exist yet.
+    pub async fn plan_update(&self, update: &Update) -> Result<UpdatePlan, ReqError> {
+        let (owner, repo, base) = (update.owner.as_str(), update.repo.as_str(), update.base.as_str());
+
+        let head_sha = match self.get_base(owner, repo, base).await {
+            Ok(head_sha) => head_sha,
+            Err(ReqError::EmptyRepository) => {
+                return Ok(UpdatePlan {
+                    status: UpdateStatus::Bootstrapped,
+                    base_sha: None,
+                    branch: base.to_string(),
+                    commit_message: INITIAL_COMMIT_MESSAGE.to_string(),
+                    changes: diff_files(&update.files, &HashMap::new()),
+                    pull_request: None,
+                });
+            }
+            Err(err) => return Err(err),
+        };
+        let parent_commit = self.get_parent_commit(owner, repo, head_sha.clone()).await?;
+        let existing: HashMap<String, (String, String)> = self
+            .get_tree(owner, repo, &parent_commit.tree.sha, true)
+            .await?
+            .into_iter()
+            .map(|entry| (entry.path, (entry.sha, entry.mode)))
+            .collect();
+        let changes = diff_files(&update.files, &existing);
+
+        if changes.iter().all(|change| change.kind == ChangeKind::Unchanged) {
+            return Ok(UpdatePlan {
+                status: UpdateStatus::NoChanges,
+                base_sha: Some(head_sha),
+                branch: String::new(),
+                commit_message: COMMIT_MESSAGE.to_string(),
+                changes,
+                pull_request: None,
+            });
+        }
+
+        // The branch name is random, so make sure the one shown isn't taken
+        let mut branch_name = random_branch_name();
+        while self.find_ref(owner, repo, &format!("refs/heads/{}", branch_name)).await?.is_some() {
+            branch_name = random_branch_name();
+        }
+        let head = if update.fork {
+            format!("<fork owner>:{}", branch_name)
+        } else {
+            branch_name.clone()
+        };
+
+        Ok(UpdatePlan {
+            status: UpdateStatus::Created,
+            base_sha: Some(head_sha),
+            commit_message: COMMIT_MESSAGE.to_string(),
+            changes,
+            pull_request: Some(NewPullRequest {
+                title: pr_title(owner, &branch_name, base),
+                head,
+                base: base.to_string(),
+                maintainer_can_modify: update.maintainer_can_modify,
+                draft: update.draft,
+            }),
+            branch: branch_name,
+        })
+    }
+
+    // Apply each update, at most `concurrency` at a time. A failure only affects
+    // its own repository; results are returned in the order of the updates.
+    pub async fn apply_updates(&self, updates: &[Update], concurrency: usize) -> Vec<Result<UpdateOutcome, ReqError>> {
+        // Collected first to keep this future Send, as in upload_blobs
+        let applies: Vec<_> = updates.iter().map(|update| self.apply_update(update)).collect();
+        stream::iter(applies)
+            .buffered(concurrency.max(1))
+            .collect()
+            .await
+    }
+}
+
+fn random_branch_name() -> String {
+    let charset = "1234567890";
+    generate(6, charset)
+}
+
+fn pr_title(owner: &str, branch_name: &str, base: &str) -> String {
+    format!("This pull request created by {} merges {} into {}", owner, branch_name, base)
+}
+
+// Compare files to the (path -> (sha, mode)) entries of the base tree
+fn diff_files(files: &[TreeFile], existing: &HashMap<String, (String, String)>) -> Vec<FileChange> {
+    files
+        .iter()
+        .map(|file| {
+            let sha = blob_sha(&file.content);
+            let kind = match existing.get(&file.path) {
+                None => ChangeKind::Added,
+                Some((existing_sha, mode)) if *existing_sha == sha && *mode == file.mode => ChangeKind::Unchanged,
+                Some(_) => ChangeKind::Modified,
+            };
+            FileChange {
+                path: file.path.clone(),
+                kind,
+                sha,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>(_: T) {}
+
+    // Async callers spawn these on multi-threaded runtimes, so their futures
+    // must stay Send. Nothing is polled; this only has to compile.
+    #[test]
+    fn futures_are_send() {
+        let client = GitHubClient::new();
+        let files = vec![TreeFile::new("README.md", "Hello World!")];
+        let update = Update::new("owner", "repo", "main", files.clone());
+        let updates = vec![update.clone()];
+        assert_send(client.create_tree("owner", "repo", "sha".to_string(), &files));
+        assert_send(client.apply_update(&update));
+        assert_send(client.plan_update(&update));
+        assert_send(client.apply_updates(&updates, 2));
+    }
+}
@@ -0,0 +1,119 @@
+// push_commit against a minimal fake GitHub API, which records the body of
+// every request it answers
+use sget_github::blocking::GitHubClient;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Requests = Arc<Mutex<Vec<(String, String, Value)>>>;
+
+fn serve(stream: TcpStream, requests: Requests) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        let mut parts = request_line.split_whitespace();
+        let method = parts.next().unwrap_or_default().to_string();
+        let path = parts.next().unwrap_or_default().to_string();
+
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+        let response = match (method.as_str(), path.as_str()) {
+            ("POST", "/repos/o/r/git/commits") => json!({
+                "sha": "new-commit",
+                "node_id": "C_1",
+                "url": "http://fake/commits/new-commit",
+                "html_url": "http://fake/new-commit",
+                "author": {"name": "a", "email": "a@example.com", "date": "2021-01-01T00:00:00Z"},
+                "committer": {"name": "a", "email": "a@example.com", "date": "2021-01-01T00:00:00Z"},
+                "message": body["message"],
+                "tree": {"sha": body["tree"], "url": "http://fake/trees"},
+                "parents": [],
+                "verification": {"verified": false, "reason": "unsigned", "signature": null, "payload": null}
+            }),
+            ("PATCH", "/repos/o/r/git/refs/heads/feature") => json!({
+                "ref": "refs/heads/feature",
+                "node_id": "R_1",
+                "url": "http://fake/refs/heads/feature",
+                "object": {"sha": body["sha"], "type": "commit", "url": "http://fake/commits"}
+            }),
+            _ => Value::Null,
+        };
+        requests.lock().unwrap().push((method, path, body));
+
+        let (status, response) = match response {
+            Value::Null => ("404 Not Found", json!({"message": "Not Found"}).to_string()),
+            response => ("200 OK", response.to_string()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+            status,
+            response.len(),
+            response
+        )
+        .unwrap();
+    }
+}
+
+fn fake_github() -> (String, Requests) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let requests = Requests::default();
+    let recorded = requests.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let recorded = recorded.clone();
+            thread::spawn(move || serve(stream, recorded));
+        }
+    });
+    (url, requests)
+}
+
+#[test]
+fn push_commit_is_a_child_of_the_base() {
+    let (url, requests) = fake_github();
+    std::env::set_var("GITHUB_API_URL", url);
+    // the fake server isn't github.com, so it gets the Enterprise token
+    std::env::set_var("GITHUB_ENTERPRISE_AUTH_TOKEN", "test-token");
+
+    let client = GitHubClient::new();
+    let commit_sha = client
+        .push_commit("refs/heads/feature".to_string(), "o", "r", "new-tree".to_string(), "base-head".to_string())
+        .unwrap();
+    assert_eq!(commit_sha, "new-commit");
+
+    let requests = requests.lock().unwrap();
+    let (_, _, commit) = requests
+        .iter()
+        .find(|(method, path, _)| method == "POST" && path == "/repos/o/r/git/commits")
+        .expect("no commit was created");
+    assert_eq!(commit["tree"], "new-tree");
+    assert_eq!(commit["parents"], json!(["base-head"]));
+
+    let (_, _, update) = requests
+        .iter()
+        .find(|(method, path, _)| method == "PATCH" && path == "/repos/o/r/git/refs/heads/feature")
+        .expect("the branch was not updated");
+    assert_eq!(update["sha"], "new-commit");
+}